@@ -0,0 +1,24 @@
+#![no_main]
+#![allow(non_upper_case_globals)]
+use libfuzzer_sys::fuzz_target;
+use once_cell::sync::Lazy;
+use tokio::runtime::Runtime;
+
+use rosu::bancho;
+use rosu::objects::user::User;
+
+static runtime: Lazy<Runtime> = Lazy::new(|| {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async { rosu::init_standalone() });
+    return rt;
+});
+
+// runs the input through the dispatcher as a request body from a fresh logged in user,
+// handlers and all. anything that needs mysql fails against the standalone pool.
+fuzz_target!(|data: &[u8]| {
+    runtime.block_on(async {
+        let mut user = User::stub(1000, "fuzzer");
+        bancho::dispatch(&mut user, data.to_vec()).await;
+        user.dequeue().await;
+    });
+});
@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rosu::packets::reader::Reader;
+
+// walks packet boundaries the same way bancho::bancho does
+fuzz_target!(|data: &[u8]| {
+    let mut reader = Reader::new(data.to_vec());
+
+    while !reader.empty() {
+        let len = match reader.read_header() {
+            Ok((_, len)) => len,
+            Err(_) => break,
+        };
+
+        if reader.read_packet(len as usize).is_err() {
+            break;
+        }
+    }
+});
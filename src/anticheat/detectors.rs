@@ -0,0 +1,280 @@
+use num_traits::FromPrimitive;
+
+use crate::anticheat::{Detector, Flag};
+use crate::constants::action::Action;
+use crate::constants::flags::LastFmFlags;
+use crate::constants::replay::ReplayAction;
+use crate::objects::mods::Mods;
+use crate::objects::user::User;
+use crate::packets::structs::{ReplayFrame, ReplayFrameBundle};
+
+// M1 & M2, K1 and K2 are sent along with them
+const CLICK_KEYS: u8 = 0b11;
+
+// keys held for less than this are faster than a keyboard or mouse can bounce
+const MIN_HOLD_MS: i32 = 10;
+const MIN_FAST_PRESSES: usize = 5;
+
+// a jump this far within this long is over 60000 osu!pixels a second
+const TELEPORT_DISTANCE: f32 = 250.0;
+const TELEPORT_MS: i32 = 4;
+const MIN_TELEPORTS: usize = 3;
+
+#[inline(always)]
+fn playing(user: &User) -> bool {
+    return user.action == Action::Playing || user.action == Action::Multiplaying;
+}
+
+#[inline(always)]
+fn standard_bundle(bundle: &ReplayFrameBundle) -> bool {
+    return ReplayAction::from_u8(bundle.action) == Some(ReplayAction::Standard);
+}
+
+// Returns how many presses were held for under MIN_HOLD_MS, out of every completed press.
+pub fn fast_presses(frames: &[ReplayFrame]) -> (usize, usize) {
+    let mut pressed_at = None;
+    let mut fast = 0;
+    let mut total = 0;
+
+    for frame in frames {
+        let down = frame.button_state & CLICK_KEYS != 0;
+
+        match (pressed_at, down) {
+            (None, true) => pressed_at = Some(frame.time),
+            (Some(start), false) => {
+                let held = frame.time - start;
+
+                // frames can jump backwards around skips and restarts
+                if held >= 0 {
+                    total += 1;
+
+                    if held < MIN_HOLD_MS {
+                        fast += 1;
+                    }
+                }
+
+                pressed_at = None;
+            }
+            _ => (),
+        }
+    }
+
+    return (fast, total);
+}
+
+// Returns how many times the cursor covered TELEPORT_DISTANCE within TELEPORT_MS.
+pub fn teleports(frames: &[ReplayFrame]) -> usize {
+    return frames
+        .windows(2)
+        .filter(|pair| {
+            let elapsed = pair[1].time - pair[0].time;
+            let distance = (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y);
+
+            (0..=TELEPORT_MS).contains(&elapsed) && distance >= TELEPORT_DISTANCE
+        })
+        .count();
+}
+
+// Mods the client would never let you play with together, for the given vanilla mode.
+pub fn impossible_mods(mods: Mods, mode: i32) -> Option<&'static str> {
+    let key_mods = Mods::KEY1
+        | Mods::KEY2
+        | Mods::KEY3
+        | Mods::KEY4
+        | Mods::KEY5
+        | Mods::KEY6
+        | Mods::KEY7
+        | Mods::KEY8
+        | Mods::KEY9
+        | Mods::KEYCOOP;
+
+    if mods.contains(Mods::RELAX | Mods::AUTOPILOT) {
+        return Some("relax with autopilot");
+    } else if mods.contains(Mods::EASY | Mods::HARDROCK) {
+        return Some("easy with hard rock");
+    } else if mods.contains(Mods::DOUBLETIME | Mods::HALFTIME) {
+        return Some("double time with half time");
+    } else if mods.contains(Mods::NIGHTCORE) && !mods.contains(Mods::DOUBLETIME) {
+        return Some("nightcore without double time");
+    } else if mods.contains(Mods::PERFECT) && !mods.contains(Mods::SUDDENDEATH) {
+        return Some("perfect without sudden death");
+    } else if mode == 3 && mods.intersects(Mods::RELAX | Mods::AUTOPILOT) {
+        return Some("relax or autopilot in mania");
+    } else if mode != 0 && mods.contains(Mods::AUTOPILOT) {
+        return Some("autopilot outside of standard");
+    } else if mode != 3 && mods.intersects(key_mods) {
+        return Some("key mods outside of mania");
+    }
+
+    return None;
+}
+
+// Flags mostly impossibly short key presses, like a tapping bot would make.
+pub struct KeyPressTiming;
+
+impl Detector for KeyPressTiming {
+    fn name(&self) -> &'static str {
+        return "key_press_timing";
+    }
+
+    fn check_frames(&self, user: &User, bundle: &ReplayFrameBundle) -> Option<Flag> {
+        // only standard frames carry clicks, and relax clicks for you
+        if user.current_mode.as_vn() != 0
+            || user.mods.intersects(Mods::RELAX | Mods::AUTOPLAY)
+            || !standard_bundle(bundle)
+        {
+            return None;
+        }
+
+        let (fast, total) = fast_presses(&bundle.frames);
+        if fast < MIN_FAST_PRESSES || fast * 2 < total {
+            return None;
+        }
+
+        return Some(Flag {
+            flags: LastFmFlags::FAST_PRESS,
+            reason: format!(
+                "{} of {} presses held for under {}ms",
+                fast, total, MIN_HOLD_MS
+            ),
+        });
+    }
+}
+
+// Flags the cursor jumping across the playfield faster than a hand can move it.
+pub struct CursorSpeed;
+
+impl Detector for CursorSpeed {
+    fn name(&self) -> &'static str {
+        return "cursor_speed";
+    }
+
+    fn check_frames(&self, user: &User, bundle: &ReplayFrameBundle) -> Option<Flag> {
+        // autopilot moves the cursor for you
+        if user.current_mode.as_vn() != 0
+            || user.mods.intersects(Mods::AUTOPILOT | Mods::AUTOPLAY)
+            || !standard_bundle(bundle)
+        {
+            return None;
+        }
+
+        let count = teleports(&bundle.frames);
+        if count < MIN_TELEPORTS {
+            return None;
+        }
+
+        return Some(Flag {
+            flags: LastFmFlags::RAW_MOUSE_DISCREPANCY,
+            reason: format!(
+                "cursor moved {}+ osu!pixels within {}ms {} times",
+                TELEPORT_DISTANCE, TELEPORT_MS, count
+            ),
+        });
+    }
+}
+
+// Flags mods that don't go together, or that disagree with what the score frames say.
+pub struct ModMismatch;
+
+impl Detector for ModMismatch {
+    fn name(&self) -> &'static str {
+        return "mod_mismatch";
+    }
+
+    fn check_status(&self, user: &User) -> Option<Flag> {
+        if !playing(user) {
+            return None;
+        }
+
+        return impossible_mods(user.mods, user.current_mode.as_vn()).map(|reason| Flag {
+            flags: LastFmFlags::INCORRECT_MOD_VALUE,
+            reason: format!("playing with {} ({})", reason, user.mods.bits()),
+        });
+    }
+
+    fn check_frames(&self, user: &User, bundle: &ReplayFrameBundle) -> Option<Flag> {
+        if !standard_bundle(bundle) {
+            return None;
+        }
+
+        let score = &bundle.score_frame;
+
+        let reason = if score.score_v2 != user.mods.contains(Mods::SCOREV2) {
+            "score v2 in the score frame doesn't match their mods"
+        } else if user.mods.intersects(Mods::SUDDENDEATH | Mods::PERFECT)
+            && score.miss > 0
+            && score.current_hp > 0
+        {
+            "still alive with misses on sudden death or perfect"
+        } else {
+            return None;
+        };
+
+        return Some(Flag {
+            flags: LastFmFlags::INCORRECT_MOD_VALUE,
+            reason: format!("{} ({})", reason, user.mods.bits()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(time: i32, x: f32, y: f32, button_state: u8) -> ReplayFrame {
+        return ReplayFrame {
+            button_state: button_state,
+            taiko_byte: 0,
+            x: x,
+            y: y,
+            time: time,
+        };
+    }
+
+    #[test]
+    fn counts_fast_presses() {
+        let frames = vec![
+            frame(0, 0.0, 0.0, 5), // K1
+            frame(40, 0.0, 0.0, 0),
+            frame(100, 0.0, 0.0, 10), // K2
+            frame(103, 0.0, 0.0, 0),
+            frame(200, 0.0, 0.0, 1), // M1, held until the end
+        ];
+
+        assert_eq!(fast_presses(&frames), (1, 2));
+    }
+
+    #[test]
+    fn smoke_isnt_a_press() {
+        let frames = vec![frame(0, 0.0, 0.0, 16), frame(1, 0.0, 0.0, 0)];
+        assert_eq!(fast_presses(&frames), (0, 0));
+    }
+
+    #[test]
+    fn counts_teleports() {
+        let frames = vec![
+            frame(0, 0.0, 0.0, 0),
+            frame(2, 300.0, 0.0, 0), // teleport
+            frame(18, 0.0, 0.0, 0),  // fast, but humanly so
+            frame(19, 10.0, 10.0, 0),
+            frame(20, 400.0, 300.0, 0), // teleport
+        ];
+
+        assert_eq!(teleports(&frames), 2);
+    }
+
+    #[test]
+    fn impossible_mod_combinations() {
+        assert_eq!(impossible_mods(Mods::HIDDEN | Mods::DOUBLETIME, 0), None);
+        assert_eq!(impossible_mods(Mods::NIGHTCORE | Mods::DOUBLETIME, 0), None);
+        assert_eq!(impossible_mods(Mods::KEY4, 3), None);
+
+        assert!(impossible_mods(Mods::RELAX | Mods::AUTOPILOT, 0).is_some());
+        assert!(impossible_mods(Mods::EASY | Mods::HARDROCK, 1).is_some());
+        assert!(impossible_mods(Mods::NIGHTCORE, 0).is_some());
+        assert!(impossible_mods(Mods::PERFECT, 0).is_some());
+        assert!(impossible_mods(Mods::RELAX, 3).is_some());
+        assert!(impossible_mods(Mods::AUTOPILOT, 2).is_some());
+        assert!(impossible_mods(Mods::KEY7, 0).is_some());
+    }
+}
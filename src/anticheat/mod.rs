@@ -0,0 +1,171 @@
+pub mod detectors;
+
+use ntex::util::Bytes;
+use once_cell::sync::OnceCell;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::cluster::{self, Event};
+use crate::constants::flags::LastFmFlags;
+use crate::metrics;
+use crate::objects::ratelimit::RateLimiter;
+use crate::objects::user::User;
+use crate::objects::writes::{unix_time, PendingWrite};
+use crate::packets::handlers;
+use crate::packets::structs::ReplayFrameBundle;
+use crate::{config, db, pending_writes, players};
+
+// each detector reports a user at most this often, they'd otherwise fire on every bundle
+const REPORT_INTERVAL: Duration = Duration::from_secs(300);
+
+// Something a detector thinks is off about a user.
+pub struct Flag {
+    pub flags: LastFmFlags,
+    pub reason: String,
+}
+
+// A heuristic run over what a client sends us. a detector only has to implement
+// the checks it cares about and get registered in `Pipeline::with_defaults`.
+pub trait Detector: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Runs on every spectator frame bundle the user sends.
+    fn check_frames(&self, _user: &User, _bundle: &ReplayFrameBundle) -> Option<Flag> {
+        return None;
+    }
+
+    // Runs whenever the user changes their status.
+    fn check_status(&self, _user: &User) -> Option<Flag> {
+        return None;
+    }
+}
+
+// Runs every registered detector and reports whatever they flag
+// to lastfm_flags and the staff channel.
+pub struct Pipeline {
+    detectors: Vec<(Box<dyn Detector>, RateLimiter)>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        return Self {
+            detectors: Vec::new(),
+        };
+    }
+
+    pub fn with_defaults() -> Self {
+        return Self::new()
+            .register(detectors::KeyPressTiming)
+            .register(detectors::CursorSpeed)
+            .register(detectors::ModMismatch);
+    }
+
+    pub fn register<D: Detector + 'static>(mut self, detector: D) -> Self {
+        self.detectors
+            .push((Box::new(detector), RateLimiter::new(REPORT_INTERVAL)));
+        return self;
+    }
+
+    pub async fn frames(&self, user: &User, bundle: &ReplayFrameBundle) {
+        for (detector, limiter) in &self.detectors {
+            if let Some(flag) = detector.check_frames(user, bundle) {
+                if limiter.check(user.id) {
+                    report(user, detector.name(), flag).await;
+                }
+            }
+        }
+    }
+
+    pub async fn status(&self, user: &User) {
+        for (detector, limiter) in &self.detectors {
+            if let Some(flag) = detector.check_status(user) {
+                if limiter.check(user.id) {
+                    report(user, detector.name(), flag).await;
+                }
+            }
+        }
+    }
+}
+
+async fn report(user: &User, detector: &'static str, flag: Flag) {
+    warn!(detector, flags = flag.flags.bits(), reason = %flag.reason, "anticheat flag");
+    metrics::ANTICHEAT_FLAGS
+        .with_label_values(&[detector])
+        .inc();
+
+    // the `anticheat` table only holds api access entries (dcid, api, allowed) and has nowhere
+    // to put a flag, so they go in lastfm_flags next to the client's own for the panel to show
+    pending_writes
+        .push(PendingWrite::LastFmFlag {
+            user_id: user.id,
+            timestamp: unix_time(),
+            flags: flag.flags.bits(),
+            text: format!("[{}] {}", detector, flag.reason),
+        })
+        .await;
+
+    let message = format!(
+        "{} ({}) was flagged by {}: {}",
+        user.username, user.id, detector, flag.reason
+    );
+
+    // the handler calling us holds this user's lock, so let it go before locking anyone else
+    tokio::spawn(notify_staff(message));
+}
+
+static bot_name: OnceCell<String> = OnceCell::new();
+
+async fn get_bot_name() -> String {
+    if let Some(name) = bot_name.get() {
+        return name.clone();
+    }
+
+    let bot_id = config.get().unwrap().bot_ids.first().copied().unwrap_or(1);
+    let row: Result<(String,), sqlx::Error> =
+        sqlx::query_as("SELECT username FROM users WHERE id = ?")
+            .bind(bot_id)
+            .fetch_one(db.get().unwrap())
+            .await;
+
+    return match row {
+        Ok((name,)) => bot_name.get_or_init(|| name).clone(),
+        Err(e) => {
+            error!(bot_id, error = %e, "failed to fetch the bot's username");
+            "rosu".to_string()
+        }
+    };
+}
+
+// Sends the message to staff as if it was said in the anticheat channel, on every instance.
+async fn notify_staff(message: String) {
+    let channel = &config.get().unwrap().anticheat_channel;
+    if channel.is_empty() {
+        return;
+    }
+
+    let bot_id = config.get().unwrap().bot_ids.first().copied().unwrap_or(1);
+    let packet_bytes =
+        handlers::channel_message(get_bot_name().await, bot_id, message, channel.clone());
+
+    cluster::relay(Event::StaffNotice {
+        data: packet_bytes.clone(),
+    })
+    .await;
+
+    notify_local_staff(packet_bytes).await;
+}
+
+// Sends to every staff member on this instance. nothing joins channels until chat is
+// implemented, so they all get it rather than just the channel's members. this locks
+// every user, so it's only ever run from its own task.
+pub async fn notify_local_staff<B: Into<Bytes>>(bytes: B) {
+    let bytes = bytes.into();
+
+    for u in players.all() {
+        let user = u.read().await;
+
+        if user.staff() {
+            user.enqueue(bytes.clone()).await;
+        }
+    }
+}
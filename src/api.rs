@@ -0,0 +1,266 @@
+use ntex::web::types::{Json, Path};
+use ntex::web::{self, App, HttpRequest, HttpResponse};
+use redis::AsyncCommands;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use tracing::info;
+
+use crate::cluster;
+use crate::constants::privileges::TokenPrivileges;
+use crate::objects::user::User;
+use crate::packets::handlers;
+use crate::{db, players, redis};
+
+// JSON API for admin panels, authenticated with a key from the `tokens` table.
+// in a cluster, sessions and channels are only the ones on the instance that's asked,
+// everything else reaches the user on whichever instance is serving them.
+//
+// GET  /api/v1/sessions              every online user
+// GET  /api/v1/sessions/{id}         one online user
+// POST /api/v1/sessions/{id}/kick    {"reason": "..."}
+// POST /api/v1/sessions/{id}/notify  {"message": "..."}
+// POST /api/v1/notify                {"message": "..."}, to everyone online
+// GET  /api/v1/channels              channels with their members
+// POST /api/v1/captures              {"userID": 1000, "enabled": true}, like peppy:capture
+pub fn scope() -> web::Scope<web::DefaultError> {
+    return web::scope("/api/v1")
+        .route("/sessions", web::get().to(list_sessions))
+        .route("/sessions/{id}", web::get().to(get_session))
+        .route("/sessions/{id}/kick", web::post().to(kick))
+        .route("/sessions/{id}/notify", web::post().to(notify_user))
+        .route("/notify", web::post().to(notify_all))
+        .route("/channels", web::get().to(list_channels))
+        .route("/captures", web::post().to(set_capture));
+}
+
+// Serves the API on its own address, so it can stay local while bancho is public.
+pub fn serve(addr: SocketAddr) -> std::io::Result<ntex::server::Server> {
+    let server = web::server(|| App::new().service(scope()))
+        .workers(1)
+        .disable_signals()
+        .bind(addr)?
+        .run();
+
+    info!(%addr, "serving the admin api");
+    return Ok(server);
+}
+
+#[derive(Deserialize)]
+pub struct KickRequest {
+    reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct NotifyRequest {
+    message: String,
+}
+
+#[derive(Deserialize)]
+pub struct CaptureRequest {
+    #[serde(rename = "userID")]
+    user_id: Option<i32>, // everyone if missing
+    enabled: bool,
+}
+
+// Publishes to one of the peppy:* channels every instance listens on.
+async fn publish(channel: &str, message: &Value) -> bool {
+    return match redis.get().unwrap().get_async_connection().await {
+        Ok(mut conn) => conn
+            .publish::<_, _, ()>(channel, message.to_string())
+            .await
+            .is_ok(),
+        _ => false,
+    };
+}
+
+#[inline(always)]
+fn error(mut response: web::HttpResponseBuilder, message: &str) -> HttpResponse {
+    return response.json(&json!({ "error": message }));
+}
+
+// Checks the request's api key (`X-Ripple-Token` or `Authorization: Bearer`)
+// against the tokens table, they're stored md5'd like ripple's api does.
+async fn authorise(req: &HttpRequest) -> Result<(), HttpResponse> {
+    let headers = req.headers();
+    let key = match headers.get("X-Ripple-Token") {
+        Some(value) => value.to_str().ok(),
+        _ => headers
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer ")),
+    };
+
+    let key = match key {
+        Some(key) if !key.is_empty() => key,
+        _ => return Err(error(HttpResponse::Unauthorized(), "missing api key")),
+    };
+
+    let row: Option<(i32,)> =
+        sqlx::query_as("SELECT privileges FROM tokens WHERE token = ? LIMIT 1")
+            .bind(format!("{:x}", md5::compute(key)))
+            .fetch_optional(db.get().unwrap())
+            .await
+            .map_err(|_| error(HttpResponse::InternalServerError(), "database error"))?;
+
+    let privileges = match row {
+        Some((privileges,)) => TokenPrivileges::from_bits_truncate(privileges),
+        _ => return Err(error(HttpResponse::Unauthorized(), "invalid api key")),
+    };
+
+    if !privileges.contains(TokenPrivileges::BANCHO) {
+        return Err(error(
+            HttpResponse::Forbidden(),
+            "api key lacks bancho privileges",
+        ));
+    }
+
+    return Ok(());
+}
+
+fn session_json(user: &User) -> Value {
+    let action: &'static str = user.action.into();
+    let mode: &'static str = user.current_mode.into();
+
+    return json!({
+        "id": user.id,
+        "username": user.username,
+        "country": user.country,
+        "restricted": user.restricted(),
+        "action": action,
+        "info_text": user.info_text,
+        "map_id": user.map_id,
+        "map_md5": user.map_md5,
+        "mode": mode,
+        "mods": user.mods.bits(),
+        "spectating": user.spectating.host(),
+        "spectators": user.spectating.spectators(),
+        "channels": user.channels.keys().collect::<Vec<&String>>(),
+        "utc_offset": user.utc_offset,
+        "idle_secs": user.last_request.elapsed().as_secs(),
+    });
+}
+
+async fn list_sessions(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    let mut sessions = Vec::new();
+    for u in players.all() {
+        sessions.push(session_json(&*u.read().await));
+    }
+
+    return HttpResponse::Ok().json(&json!({ "sessions": sessions }));
+}
+
+async fn get_session(req: HttpRequest, user_id: Path<i32>) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    return match players.get_id(user_id.into_inner()) {
+        Some(u) => HttpResponse::Ok().json(&session_json(&*u.read().await)),
+        _ => error(HttpResponse::NotFound(), "user is not online"),
+    };
+}
+
+async fn kick(req: HttpRequest, user_id: Path<i32>, body: Json<KickRequest>) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    let user_id = user_id.into_inner();
+    match players.get_id(user_id) {
+        Some(u) => u.write().await.kick(&body.reason).await,
+        // the instance serving them kicks them
+        _ if cluster::is_online(user_id).await => {
+            let message = json!({ "userID": user_id, "reason": body.reason });
+            if !publish("peppy:disconnect", &message).await {
+                return error(HttpResponse::InternalServerError(), "redis error");
+            }
+        }
+        _ => return error(HttpResponse::NotFound(), "user is not online"),
+    }
+
+    return HttpResponse::Ok().json(&json!({ "kicked": true }));
+}
+
+async fn notify_user(
+    req: HttpRequest,
+    user_id: Path<i32>,
+    body: Json<NotifyRequest>,
+) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    let user_id = user_id.into_inner();
+    if players.get_id(user_id).is_none() && !cluster::is_online(user_id).await {
+        return error(HttpResponse::NotFound(), "user is not online");
+    }
+
+    cluster::enqueue_to(&[user_id], handlers::notification(&body.message)).await;
+
+    return HttpResponse::Ok().json(&json!({ "sent": 1 }));
+}
+
+async fn notify_all(req: HttpRequest, body: Json<NotifyRequest>) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    cluster::broadcast(handlers::notification(&body.message)).await;
+
+    return HttpResponse::Ok().json(&json!({ "sent": players.player_count() }));
+}
+
+async fn list_channels(req: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    // there's no global channel list yet, so gather them from whoever is in them
+    let mut channels = BTreeMap::new();
+    for u in players.all() {
+        for (name, channel) in &u.read().await.channels {
+            channels
+                .entry(name.clone())
+                .or_insert_with(|| channel.clone());
+        }
+    }
+
+    let channels = channels
+        .values()
+        .map(|channel| {
+            json!({
+                "name": channel.name,
+                "description": channel.description,
+                "autojoin": channel.autojoin,
+                "members": channel.users.ids(),
+            })
+        })
+        .collect::<Vec<Value>>();
+
+    return HttpResponse::Ok().json(&json!({ "channels": channels }));
+}
+
+// Goes through peppy:capture rather than straight to the capture list,
+// so every instance in a cluster starts capturing and not just this one.
+async fn set_capture(req: HttpRequest, body: Json<CaptureRequest>) -> HttpResponse {
+    if let Err(response) = authorise(&req).await {
+        return response;
+    }
+
+    let message = match body.user_id {
+        Some(user_id) => json!({ "userID": user_id, "enabled": body.enabled }),
+        _ => json!({ "enabled": body.enabled }),
+    };
+
+    if !publish("peppy:capture", &message).await {
+        return error(HttpResponse::InternalServerError(), "redis error");
+    }
+
+    return HttpResponse::Ok().json(&message);
+}
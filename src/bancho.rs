@@ -2,186 +2,458 @@ use bcrypt;
 use ntex::http::HeaderMap;
 use ntex::web::{self, HttpRequest, HttpResponse};
 use std::time::Instant;
+use tracing::{debug, field, info, info_span, warn, Instrument, Span};
 use uuid::Uuid;
 
 use maxminddb::geoip2;
+use std::fmt;
 use std::net::IpAddr;
 use std::str::FromStr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::cluster;
 use crate::constants::packets::Packets;
+use crate::metrics;
 use crate::objects::user::User;
 use crate::packets::handlers::{self, PACKET_HANDLERS, RESTRICTED_PACKET_HANDLERS};
 use crate::packets::reader::Reader;
 
-use crate::{bcrypt_cache, players, reader};
+use crate::capture::Direction;
+use crate::constants::privileges::BanchoPrivileges;
+use crate::tasks;
+use crate::{
+    bcrypt_cache, captures, config, pending_writes, players, reader, settings, shutting_down,
+};
 
 use num_traits::FromPrimitive;
 
-#[inline(always)]
-async fn login(data: Vec<u8>, headers: &HeaderMap) -> (String, Vec<u8>) {
-    let start = Instant::now();
+// Everything that can stop a bancho request from being served normally.
+#[derive(Debug, PartialEq)]
+pub enum BanchoError {
+    // the request itself is malformed, answered with HTTP 400
+    BadRequest(&'static str),
+    // the login was understood but refused, the client is notified why
+    LoginRejected(&'static str),
+    // the token doesn't belong to an online player, the client is told to relog
+    SessionNotFound,
+    // we're shutting down, the client is told to try again once we're back
+    ShuttingDown,
+}
 
-    let mut return_data: Vec<u8> = Vec::new();
+impl BanchoError {
+    pub fn into_response(self) -> HttpResponse {
+        match self {
+            BanchoError::BadRequest(reason) => {
+                return HttpResponse::BadRequest().body(format!("rosu: {}", reason));
+            }
+            BanchoError::LoginRejected(reason) => {
+                let mut return_data = handlers::user_id(-1);
+                return_data.extend(handlers::notification(reason));
+
+                return HttpResponse::Ok()
+                    .header("cho-token", "no")
+                    .body(return_data);
+            }
+            BanchoError::SessionNotFound => {
+                return HttpResponse::Ok().body(handlers::server_restart(0));
+            }
+            BanchoError::ShuttingDown => {
+                return HttpResponse::Ok()
+                    .header("cho-token", "no")
+                    .body(handlers::server_restart(tasks::RECONNECT_DELAY_MS));
+            }
+        }
+    }
+}
+
+// The parsed body of a login request:
+// username\npassword\nosu_ver|utc_offset|display_city|client_hashes|private_dms\n
+#[derive(PartialEq)]
+pub struct LoginData {
+    pub username: String,
+    pub password: String,
+    pub osu_ver: String,
+    pub utc_offset: i32,
+    pub osu_md5: String,
+    pub mac_md5: String,
+    pub uninstall_md5: String,
+    pub disk_md5: String,
+    pub private_dms: bool,
+}
+
+// the password and hardware hashes never make it into logs
+impl fmt::Debug for LoginData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f
+            .debug_struct("LoginData")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("osu_ver", &self.osu_ver)
+            .field("utc_offset", &self.utc_offset)
+            .field("client_hashes", &"<redacted>")
+            .field("private_dms", &self.private_dms)
+            .finish();
+    }
+}
+
+impl LoginData {
+    pub fn parse(data: &[u8]) -> Result<Self, BanchoError> {
+        let login_str =
+            std::str::from_utf8(data).map_err(|_| BanchoError::BadRequest("login is not utf-8"))?;
+
+        let login_data = login_str.split("\n").collect::<Vec<&str>>();
+        if login_data.len() != 4 {
+            return Err(BanchoError::BadRequest("malformed login"));
+        }
+
+        let client_info = login_data[2].split("|").collect::<Vec<&str>>();
+        if client_info.len() != 5 {
+            return Err(BanchoError::BadRequest("malformed client info"));
+        }
+
+        let utc_offset: i32 = client_info[1]
+            .parse()
+            .map_err(|_| BanchoError::BadRequest("malformed utc offset"))?;
+
+        // it's sent as (offset + 24) in a byte with the presence
+        if !(-24..=24).contains(&utc_offset) {
+            return Err(BanchoError::BadRequest("utc offset out of range"));
+        }
 
-    let login_str = String::from_utf8(data).unwrap();
-    let login_data = login_str
-        .split("\n")
-        .map(|chunk| chunk.to_owned())
-        .collect::<Vec<String>>();
+        // osu_md5:adapters:adapters_md5:uninstall_md5:disk_md5:
+        let client_hashes = client_info[3]
+            .strip_suffix(":")
+            .unwrap_or(client_info[3])
+            .split(":")
+            .collect::<Vec<&str>>();
 
-    if login_data.len() != 4 {
-        return ("no".to_string(), return_data); // invalid request
+        if client_hashes.len() < 5 {
+            return Err(BanchoError::BadRequest("malformed client hashes"));
+        }
+
+        return Ok(Self {
+            username: login_data[0].to_string(),
+            password: login_data[1].to_string(),
+            osu_ver: client_info[0].to_string(), // TODO: validate
+            utc_offset: utc_offset,
+            osu_md5: client_hashes[0].to_string(),
+            mac_md5: client_hashes[2].to_string(),
+            uninstall_md5: client_hashes[3].to_string(),
+            disk_md5: client_hashes[4].to_string(),
+            private_dms: client_info[4] == "1",
+        });
     }
+}
 
-    let username = login_data[0].clone();
-    let password = login_data[1].clone();
+#[inline(always)]
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    return headers.get(name).and_then(|value| value.to_str().ok());
+}
 
-    let client_info = login_data[2].split("|").collect::<Vec<&str>>();
-    if client_info.len() != 5 {
-        return ("no".to_string(), return_data); // invalid request
+// Whether the request came from an osu! client, based on its user agent.
+pub fn is_osu_client(headers: &HeaderMap) -> bool {
+    return header_str(headers, "User-Agent") == Some("osu!");
+}
+
+// Works out the client's ip from whichever proxy headers are present.
+pub fn client_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    if let Some(ip) = header_str(headers, "CF-Connecting-IP") {
+        return IpAddr::from_str(ip.trim()).ok();
     }
 
-    let osu_ver = client_info[0]; // TODO: validate
-    let utc_offset: i32 = client_info[1].parse().unwrap();
+    // the first forward is the client when the request went through more than one proxy
+    let forwards = header_str(headers, "X-Forwarded-For")
+        .map(|value| value.split(",").collect::<Vec<&str>>())
+        .unwrap_or_default();
 
-    let mut client_str = client_info[3].to_string();
-    client_str.pop();
+    let ip = match header_str(headers, "X-Real-IP") {
+        Some(ip) if forwards.len() < 2 => ip,
+        _ => *forwards.first()?,
+    };
 
-    // TODO: use these.
-    let client_hashes = client_str.split(":").collect::<Vec<&str>>();
-    let osu_md5 = client_hashes[0];
-    let mac_md5 = client_hashes[2];
-    let uninstall_md5 = client_hashes[3];
-    let disk_md5 = client_hashes[4];
+    return IpAddr::from_str(ip.trim()).ok();
+}
+
+#[inline(always)]
+fn geolocate(ip: IpAddr) -> Option<(f32, f32)> {
+    let city: geoip2::City = reader.get()?.lookup(ip).ok()?;
+    let location = city.location?;
 
-    let private_dms = client_info[4] == "1";
+    return Some((location.longitude? as f32, location.latitude? as f32));
+}
+
+// Finds the online player a request's osu-token belongs to.
+pub fn session(headers: &HeaderMap) -> Result<Arc<RwLock<User>>, BanchoError> {
+    let token =
+        header_str(headers, "osu-token").ok_or(BanchoError::BadRequest("malformed token"))?;
+
+    return players.get_token(token).ok_or(BanchoError::SessionNotFound);
+}
+
+#[inline(always)]
+async fn login(data: Vec<u8>, headers: &HeaderMap) -> Result<(String, Vec<u8>), BanchoError> {
+    let start = Instant::now();
+
+    let mut return_data: Vec<u8> = Vec::new();
+
+    if shutting_down.load(Ordering::SeqCst) {
+        return Err(BanchoError::ShuttingDown);
+    }
+
+    let login = LoginData::parse(&data)?;
+    Span::current().record("username", login.username.as_str());
+    debug!(?login, "login request");
+
+    // TODO: use the client hashes.
+    let username = login.username;
+    let password = login.password;
 
     let token = Uuid::new_v4();
-    let user_result = User::from_sql(&username, token, osu_ver, utc_offset).await;
+    let user_result = User::from_sql(&username, token, &login.osu_ver, login.utc_offset).await;
 
     let mut user = match user_result {
         Some(user) => user,
-        _ => {
-            return ("no".to_string(), handlers::user_id(-1));
-        }
+        _ => return Err(BanchoError::LoginRejected("Unknown username")),
     };
 
+    user.osu_md5 = login.osu_md5;
+
+    Span::current().record("user_id", user.id);
+    Span::current().record("token", user.token.as_str());
+
     // verify password, using web::block to avoid blocking the thread
     let bcrypt = user.password_md5.clone();
 
     let to_cache = user.password_md5.clone();
     let md5 = password.clone();
 
-    let valid_password: bool;
-    if !bcrypt_cache.lock().await.contains_key(&md5) {
-        valid_password = web::block(move || bcrypt::verify(password, &bcrypt))
+    let valid_password = if !bcrypt_cache.lock().await.contains_key(&md5) {
+        // a corrupt hash in the db can't ever match
+        web::block(move || bcrypt::verify(password, &bcrypt))
             .await
-            .unwrap();
+            .unwrap_or(false)
     } else {
-        valid_password = bcrypt_cache.lock().await.get(&md5).unwrap() == &to_cache;
-    }
+        bcrypt_cache.lock().await.get(&md5) == Some(&to_cache)
+    };
 
     if !valid_password {
-        return_data.extend(handlers::user_id(-1));
-        return_data.extend(handlers::notification("Incorrect password"));
-
-        return ("no".to_string(), return_data);
+        return Err(BanchoError::LoginRejected("Incorrect password"));
     }
 
     bcrypt_cache.lock().await.insert(md5, to_cache);
 
-    // parse geoloc
-    let ip: &str;
-
-    if headers.contains_key("CF-Connecting-IP") {
-        ip = headers.get("CF-Connecting-IP").unwrap().to_str().unwrap();
-    } else {
-        let forwards = headers // this is fucking unbelievable HAHAHA
-            .get("X-Forwarded-For")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .split(",")
-            .collect::<Vec<&str>>();
+    if settings.read().await.maintenance && !user.staff() {
+        return Err(BanchoError::LoginRejected(
+            "rosu is currently in maintenance mode, please try again later.",
+        ));
+    }
 
-        if forwards.len() != 1 {
-            ip = &forwards[0];
-        } else {
-            ip = headers.get("X-Real-IP").unwrap().to_str().unwrap();
+    // parse geoloc, players we can't locate just show up at 0, 0
+    match client_ip(headers).and_then(geolocate) {
+        Some((long, lat)) => {
+            user.long = long;
+            user.lat = lat;
         }
+        _ => warn!("couldn't geolocate"),
     }
 
-    let geoloc_ip: IpAddr = FromStr::from_str(ip).unwrap();
-    let city: geoip2::City = reader.lookup(geoloc_ip).unwrap();
+    // TODO: hardware checks, clan
 
-    let location = city.location.unwrap();
-    user.long = location.longitude.unwrap() as f32;
-    user.lat = location.latitude.unwrap() as f32;
+    let bancho_settings = settings.read().await;
 
-    // TODO: hardware checks, clan
+    if bancho_settings.free_direct {
+        // supporter is what unlocks osu!direct client-side
+        user.bancho_priv |= BanchoPrivileges::SUPPORTER;
+    }
 
-    return_data.extend(handlers::protocol_version(19));
+    return_data.extend(handlers::protocol_version(
+        config.get().unwrap().protocol_version,
+    ));
     return_data.extend(handlers::user_id(user.id));
     return_data.extend(handlers::bancho_privileges(user.bancho_priv.value()));
 
     return_data.extend(handlers::channel_info_end());
-    return_data.extend(handlers::main_menu_icon("", "")); // empty icon & url for now
+
+    match &bancho_settings.menu_icon {
+        Some((icon, link)) => return_data.extend(handlers::main_menu_icon(icon, link)),
+        _ => return_data.extend(handlers::main_menu_icon("", "")),
+    }
+
     return_data.extend(handlers::friends_list(&user));
     return_data.extend(handlers::silence_end(0));
 
     return_data.extend(handlers::user_presence(&user));
     return_data.extend(handlers::user_stats(&user));
 
-    players.add_player(user).await;
+    // the client asks for presences of whoever it needs out of these
+    let mut visible_ids = vec![user.id];
+    for (uid, snapshot) in players.snapshots() {
+        if uid != user.id && !snapshot.restricted() && user.presence.wants(uid) {
+            visible_ids.push(uid);
+        }
+    }
+
+    let mut remote_ids = cluster::remote_ids().await;
+    remote_ids.retain(|uid| *uid != user.id && user.presence.wants(*uid));
+    visible_ids.extend(remote_ids);
+    return_data.extend(handlers::user_presence_bundle(visible_ids));
+
+    if let Some(notif) = &bancho_settings.login_notification {
+        return_data.extend(handlers::notification(notif));
+    }
+
+    if bancho_settings.maintenance {
+        // only staff can get this far while maintenance is on
+        return_data.extend(handlers::notification(
+            "rosu is in maintenance mode, only staff can log in.",
+        ));
+    }
+
+    if user.restricted() {
+        if let Some(notif) = &bancho_settings.restricted_notification {
+            return_data.extend(handlers::notification(notif));
+        }
+    }
+
+    drop(bancho_settings);
+
+    pending_writes.touch(user.id);
+
+    // announced before adding them, so only everyone else hears about it
+    if !user.restricted() {
+        cluster::broadcast_presence(user.id, handlers::user_presence_single(user.id)).await;
+    }
+
+    // an older session they had here is replaced, other instances drop theirs on hearing of this one
+    cluster::drop_stale_session(user.id, &user.token).await;
+    cluster::logged_in(&user).await;
+    players.add_player(user);
     return_data.extend(handlers::notification(
         format!(
             "Welcome to ROsu!\n\nTime Elapsed: {:.2?}\nPlayers online: {}",
             start.elapsed(),
-            players.player_count().await
+            players.player_count()
         )
         .as_str(),
     ));
 
-    println!("{} has logged in!", &username);
-    return (token.to_string(), return_data);
+    info!(elapsed = ?start.elapsed(), "logged in");
+    return Ok((token.to_string(), return_data));
 }
 
 pub async fn bancho(req: HttpRequest, _data: Vec<u8>) -> HttpResponse {
     if !req.headers().contains_key("osu-token") {
-        let (token, login_data) = login(_data, &req.headers()).await;
-
-        if login_data.len() == 0 {
-            // invalid request
-            return HttpResponse::Ok().header("cho-token", "no").body("");
+        let span = info_span!(
+            "login",
+            username = field::Empty,
+            user_id = field::Empty,
+            token = field::Empty
+        );
+
+        let start = Instant::now();
+        let result = login(_data, req.headers()).instrument(span.clone()).await;
+
+        if let Err(e) = &result {
+            span.in_scope(|| info!(error = ?e, "login failed"));
         }
 
-        let packet_data = unsafe { String::from_utf8_unchecked(login_data) };
-        return HttpResponse::Ok()
-            .header("cho-token", token)
-            .body(packet_data);
+        metrics::LOGIN_LATENCY.observe(start.elapsed().as_secs_f64());
+        metrics::LOGINS
+            .with_label_values(&[match &result {
+                Ok(_) => "success",
+                Err(BanchoError::BadRequest(_)) => "bad_request",
+                Err(BanchoError::LoginRejected(_)) => "rejected",
+                Err(BanchoError::SessionNotFound) => "session_not_found",
+                Err(BanchoError::ShuttingDown) => "shutting_down",
+            }])
+            .inc();
+
+        return match result {
+            Ok((token, login_data)) => HttpResponse::Ok()
+                .header("cho-token", token)
+                .body(login_data),
+            Err(e) => e.into_response(),
+        };
     }
 
     // already logged in client-side
-    let token = req.headers().get("osu-token").unwrap().to_str().unwrap();
+    let user: Arc<RwLock<User>> = match session(req.headers()) {
+        Ok(user) => user, // arc'd player, we will read from the arc below
+        Err(BanchoError::SessionNotFound) if cluster::enabled() => {
+            // another instance might have been serving them
+            let token = header_str(req.headers(), "osu-token").unwrap_or_default();
+            match cluster::take_over(token).await {
+                Some(user) => user,
+                _ => return BanchoError::SessionNotFound.into_response(),
+            }
+        }
+        Err(e) => return e.into_response(),
+    };
 
-    let user: Arc<RwLock<User>>; // arc'd player, we will read from the arc below
-    match players.get_token(token).await {
-        Some(u) => user = u,
-        _ => {
-            let return_vec = handlers::server_restart(0);
+    let mut player = user.write().await; // get readable player
+    let span = info_span!(
+        "session",
+        user_id = player.id,
+        username = %player.username,
+        token = %player.token
+    );
+
+    return handle_packets(&mut player, _data).instrument(span).await;
+}
 
-            return HttpResponse::Ok().body(unsafe { String::from_utf8_unchecked(return_vec) });
-        }
+// Runs every packet in a logged in client's request through the handlers
+// and responds with whatever has been queued for them.
+async fn handle_packets(player: &mut User, data: Vec<u8>) -> HttpResponse {
+    player.last_request = Instant::now();
+    pending_writes.touch(player.id);
+
+    captures.record(player.id, Direction::Inbound, &data).await;
+    dispatch(player, data).await;
+
+    let return_data = player.dequeue().await;
+    captures
+        .record(player.id, Direction::Outbound, &return_data)
+        .await;
+
+    if player.kicked {
+        player.logout().await;
     }
 
-    let mut player = user.write().await; // get readable player
-    let mut _reader = Reader::new(_data);
+    return HttpResponse::Ok().body(return_data);
+}
+
+// Runs every packet in a request body through its handler, whatever they send
+// back is left in the player's queue. also driven by rosu-replay and the fuzz targets.
+pub async fn dispatch(player: &mut User, data: Vec<u8>) {
+    let mut _reader = Reader::new(data);
 
     while !_reader.empty() {
-        let (id, len) = _reader.read_header();
-        let packet = Packets::from_i32(id).unwrap();
+        let (id, len) = match _reader.read_header() {
+            Ok(header) => header,
+            Err(e) => {
+                // can't find the next packet boundary, drop the rest of the body
+                warn!(error = %e, "dropping request body");
+                break;
+            }
+        };
+
+        let mut packet_reader = match _reader.read_packet(len as usize) {
+            Ok(packet_reader) => packet_reader,
+            Err(e) => {
+                warn!(error = %e, "dropping request body");
+                break;
+            }
+        };
+
+        let packet = match Packets::from_i32(id) {
+            Some(packet) => packet,
+            _ => {
+                warn!(id, "skipping unknown packet");
+                continue;
+            }
+        };
 
         // &* lmao
         let mut handler_map = &*PACKET_HANDLERS;
@@ -191,21 +463,220 @@ pub async fn bancho(req: HttpRequest, _data: Vec<u8>) -> HttpResponse {
 
         if handler_map.contains_key(&packet) {
             let callback = handler_map[&packet];
-            let should_increment = callback(&mut player, &mut _reader).await;
-
-            if should_increment {
-                _reader.incr_offset(len as usize);
+            let packet_name: &'static str = packet.into();
+
+            let start = Instant::now();
+            let result = callback(player, &mut packet_reader)
+                .instrument(info_span!("packet", packet = packet_name))
+                .await;
+
+            metrics::PACKETS_HANDLED
+                .with_label_values(&[packet_name])
+                .inc();
+            metrics::PACKET_LATENCY
+                .with_label_values(&[packet_name])
+                .observe(start.elapsed().as_secs_f64());
+
+            if let Err(e) = result {
+                warn!(packet = packet_name, error = %e, "failed to decode packet");
+                continue;
             }
 
             if packet != Packets::OSU_PING {
-                println!("Packet {:?} handled for {}", packet, player.username);
+                debug!(packet = packet_name, elapsed = ?start.elapsed(), "packet handled");
             }
-        } else {
-            _reader.incr_offset(len as usize);
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ntex::http::header::{HeaderName, HeaderValue};
+    use ntex::http::StatusCode;
+
+    const CLIENT_INFO: &str = "b20220101|0|1|osu:adapters:adapters:uninstall:disk:|0";
+
+    fn login_body(client_info: &str) -> Vec<u8> {
+        return format!("tsunyoku\npassword_md5\n{}\n", client_info).into_bytes();
+    }
+
+    fn headers(pairs: &[(&'static str, &[u8])]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(
+                HeaderName::from_static(name),
+                HeaderValue::from_bytes(value).unwrap(),
+            );
+        }
+
+        return map;
+    }
 
-    let return_data = player.dequeue().await;
-    let packet_data = unsafe { String::from_utf8_unchecked(return_data) };
-    return HttpResponse::Ok().body(packet_data);
+    #[test]
+    fn parses_valid_login() {
+        let login = LoginData::parse(&login_body(CLIENT_INFO)).unwrap();
+
+        assert_eq!(login.username, "tsunyoku");
+        assert_eq!(login.password, "password_md5");
+        assert_eq!(login.osu_ver, "b20220101");
+        assert_eq!(login.utc_offset, 0);
+        assert_eq!(login.osu_md5, "osu");
+        assert_eq!(login.disk_md5, "disk");
+        assert!(!login.private_dms);
+    }
+
+    #[test]
+    fn rejects_non_utf8_login() {
+        let mut body = login_body(CLIENT_INFO);
+        body[0] = 0xff;
+
+        assert_eq!(
+            LoginData::parse(&body),
+            Err(BanchoError::BadRequest("login is not utf-8"))
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_line_count() {
+        assert_eq!(
+            LoginData::parse(b"tsunyoku\npassword_md5"),
+            Err(BanchoError::BadRequest("malformed login"))
+        );
+        assert_eq!(
+            LoginData::parse(b""),
+            Err(BanchoError::BadRequest("malformed login"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_client_info() {
+        assert_eq!(
+            LoginData::parse(&login_body("b20220101|0|1")),
+            Err(BanchoError::BadRequest("malformed client info"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_utc_offset() {
+        for offset in &["", "utc", "99999999999"] {
+            let client_info = format!("b20220101|{}|1|osu:a:a:u:d:|0", offset);
+
+            assert_eq!(
+                LoginData::parse(&login_body(&client_info)),
+                Err(BanchoError::BadRequest("malformed utc offset"))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_utc_offset() {
+        for offset in &["-25", "25", "-300", "1000"] {
+            let client_info = format!("b20220101|{}|1|osu:a:a:u:d:|0", offset);
+
+            assert_eq!(
+                LoginData::parse(&login_body(&client_info)),
+                Err(BanchoError::BadRequest("utc offset out of range"))
+            );
+        }
+
+        for offset in &["-24", "24"] {
+            let client_info = format!("b20220101|{}|1|osu:a:a:u:d:|0", offset);
+            assert!(LoginData::parse(&login_body(&client_info)).is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_missing_client_hashes() {
+        for hashes in &["", ":", "osu:adapters:", "osu:adapters:adapters:uninstall:"] {
+            let client_info = format!("b20220101|0|1|{}|0", hashes);
+
+            assert_eq!(
+                LoginData::parse(&login_body(&client_info)),
+                Err(BanchoError::BadRequest("malformed client hashes"))
+            );
+        }
+    }
+
+    #[test]
+    fn detects_osu_client() {
+        assert!(is_osu_client(&headers(&[("user-agent", b"osu!")])));
+        assert!(!is_osu_client(&headers(&[("user-agent", b"curl/7.81.0")])));
+        assert!(!is_osu_client(&headers(&[("user-agent", b"\xff")])));
+        assert!(!is_osu_client(&headers(&[])));
+    }
+
+    #[test]
+    fn finds_client_ip() {
+        let ip = |s: &str| Some(IpAddr::from_str(s).unwrap());
+
+        assert_eq!(
+            client_ip(&headers(&[
+                ("cf-connecting-ip", b"1.1.1.1"),
+                ("x-real-ip", b"2.2.2.2"),
+            ])),
+            ip("1.1.1.1")
+        );
+        assert_eq!(
+            client_ip(&headers(&[
+                ("x-forwarded-for", b"3.3.3.3, 4.4.4.4"),
+                ("x-real-ip", b"4.4.4.4"),
+            ])),
+            ip("3.3.3.3")
+        );
+        assert_eq!(
+            client_ip(&headers(&[
+                ("x-forwarded-for", b"3.3.3.3"),
+                ("x-real-ip", b"4.4.4.4"),
+            ])),
+            ip("4.4.4.4")
+        );
+        assert_eq!(
+            client_ip(&headers(&[("x-forwarded-for", b"3.3.3.3")])),
+            ip("3.3.3.3")
+        );
+        assert_eq!(client_ip(&headers(&[("x-real-ip", b"::1")])), ip("::1"));
+    }
+
+    #[test]
+    fn tolerates_missing_or_malformed_ip() {
+        assert_eq!(client_ip(&headers(&[])), None);
+        assert_eq!(client_ip(&headers(&[("x-real-ip", b"localhost")])), None);
+        assert_eq!(client_ip(&headers(&[("cf-connecting-ip", b"\xff")])), None);
+        assert_eq!(client_ip(&headers(&[("x-forwarded-for", b"")])), None);
+    }
+
+    #[test]
+    fn rejects_malformed_token() {
+        assert_eq!(
+            session(&headers(&[("osu-token", b"\xff")])).err(),
+            Some(BanchoError::BadRequest("malformed token"))
+        );
+    }
+
+    #[test]
+    fn unknown_token_restarts_client() {
+        assert_eq!(
+            session(&headers(&[("osu-token", b"not-a-session")])).err(),
+            Some(BanchoError::SessionNotFound)
+        );
+    }
+
+    #[test]
+    fn maps_errors_to_responses() {
+        let response = BanchoError::BadRequest("malformed login").into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = BanchoError::LoginRejected("Incorrect password").into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("cho-token").unwrap(), "no");
+
+        let response = BanchoError::SessionNotFound.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("cho-token").is_none());
+
+        let response = BanchoError::ShuttingDown.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("cho-token").unwrap(), "no");
+    }
 }
@@ -0,0 +1,129 @@
+// Pretty-prints raw bancho traffic, packet by packet.
+//
+// usage: rosu-dissect [file]
+// reads stdin if no file is given. input can be raw bytes or a hex dump
+// (whitespace, `0x` prefixes and `xxd`-style offsets are ignored).
+
+#![allow(clippy::needless_return)]
+
+use num_traits::FromPrimitive;
+use std::env;
+use std::io::{self, Read};
+use std::process;
+
+use rosu::constants::packets::Packets;
+use rosu::packets::reader::Reader;
+use rosu::packets::structs;
+
+fn to_hex(bytes: &[u8]) -> String {
+    return bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(" ");
+}
+
+// hex dumps are pure ascii, so anything else is treated as raw bytes
+fn parse_hex(input: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(input).ok()?;
+    let mut digits = String::new();
+
+    for line in text.lines() {
+        // drop `00000010:` style offsets from xxd output
+        let line = match line.find(':') {
+            Some(idx) => &line[idx + 1..],
+            None => line,
+        };
+
+        // and xxd's ascii column, which is separated by two spaces
+        let line = match line.find("  ") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        for word in line.split(|c: char| c.is_whitespace() || c == ',') {
+            let word = word.trim_start_matches("0x");
+            if !word.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+
+            digits.push_str(word);
+        }
+    }
+
+    if digits.is_empty() || digits.len() % 2 == 1 {
+        return None;
+    }
+
+    return (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect();
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let input = match args.get(1) {
+        Some(path) => std::fs::read(path),
+        None => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf).map(|_| buf)
+        }
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("failed to read input: {}", e);
+        process::exit(1);
+    });
+
+    let data = parse_hex(&input).unwrap_or(input);
+    let mut reader = Reader::new(data);
+
+    while !reader.empty() {
+        let offset = reader.offset();
+
+        let (id, len) = match reader.read_header() {
+            Ok(header) => header,
+            Err(e) => {
+                println!("{:#06x}: bad header: {}", offset, e);
+                process::exit(1);
+            }
+        };
+
+        let mut body = match reader.read_packet(len as usize) {
+            Ok(body) => body,
+            Err(e) => {
+                println!("{:#06x}: packet {} ({} bytes): {}", offset, id, len, e);
+                process::exit(1);
+            }
+        };
+
+        let packet = match Packets::from_i32(id) {
+            Some(packet) => packet,
+            _ => {
+                let raw = body.read_raw().unwrap_or_default();
+                println!("{:#06x}: unknown packet {} ({} bytes)", offset, id, len);
+                println!("  {}", to_hex(&raw));
+                continue;
+            }
+        };
+
+        println!("{:#06x}: {:?} ({} bytes)", offset, packet, len);
+
+        match structs::decode_packet(packet, &mut body) {
+            Ok(decoded) => {
+                println!("{:#?}", decoded);
+
+                if !body.empty() {
+                    let trailing = body.read_raw().unwrap_or_default();
+                    println!("  trailing bytes: {}", to_hex(&trailing));
+                }
+            }
+            Err(e) => {
+                body.seek(0);
+                println!("  failed to decode: {}", e);
+                println!("  {}", to_hex(&body.read_raw().unwrap_or_default()));
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Replays a session capture (see `peppy:capture`) through rosu's packet
+// dispatcher, in the same order and with the same packet boundaries the
+// server saw them, and compares what gets sent back against the captured
+// responses so protocol bugs reported by players can be reproduced.
+//
+// every captured user is played by a stub session. there's no database behind
+// it, so handlers that need one fail just like they would with mysql down.
+//
+// usage: rosu-replay <capture file> [user id]
+
+#![allow(clippy::needless_return)]
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
+
+use rosu::bancho;
+use rosu::capture::{CaptureRecord, Direction};
+use rosu::objects::user::User;
+use rosu::packets::reader::Reader;
+use rosu::players;
+
+// splits a response body into its packets, (id, whole packet including the header)
+fn split_packets(data: &[u8]) -> Vec<(i32, Vec<u8>)> {
+    let mut reader = Reader::new(data.to_vec());
+    let mut packets = Vec::new();
+
+    while !reader.empty() {
+        let start = reader.offset();
+        let (id, len) = match reader.read_header() {
+            Ok(header) => header,
+            Err(_) => break,
+        };
+
+        if reader.read_packet(len as usize).is_err() {
+            break;
+        }
+
+        packets.push((id, data[start..reader.offset()].to_vec()));
+    }
+
+    return packets;
+}
+
+// compares the replayed response with the captured one, returning how many packets differed
+fn compare(captured: &[u8], replayed: &[u8]) -> usize {
+    if captured == replayed {
+        println!("    = {} bytes match", captured.len());
+        return 0;
+    }
+
+    let captured = split_packets(captured);
+    let replayed = split_packets(replayed);
+    let mut mismatches = 0;
+
+    for i in 0..captured.len().max(replayed.len()) {
+        match (captured.get(i), replayed.get(i)) {
+            (Some((id, a)), Some((replayed_id, b))) if id == replayed_id => {
+                if a != b {
+                    println!(
+                        "    ~ packet {} differs ({} vs {} bytes)",
+                        id,
+                        a.len(),
+                        b.len()
+                    );
+                    mismatches += 1;
+                }
+            }
+            (Some((id, _)), Some((replayed_id, _))) => {
+                println!("    ! expected packet {}, got {}", id, replayed_id);
+                mismatches += 1;
+            }
+            (Some((id, _)), None) => {
+                println!("    - packet {} was not sent", id);
+                mismatches += 1;
+            }
+            (None, Some((id, _))) => {
+                println!("    + packet {} was not captured", id);
+                mismatches += 1;
+            }
+            (None, None) => {}
+        }
+    }
+
+    return mismatches;
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <capture file> [user id]", args[0]);
+        process::exit(2);
+    }
+
+    let user_filter: Option<i32> = args.get(2).map(|id| id.parse().expect("invalid user id"));
+
+    let bytes = std::fs::read(&args[1]).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", args[1], e);
+        process::exit(1);
+    });
+
+    let records = CaptureRecord::read_all(bytes).unwrap_or_else(|e| {
+        eprintln!("corrupt capture file: {}", e);
+        process::exit(1);
+    });
+
+    // the dispatcher reports packets it couldn't decode or handle as warnings
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .init();
+    rosu::init_standalone();
+
+    let start = records.first().map_or(0, |r| r.timestamp_ms);
+    let mut replayed: HashMap<i32, Vec<u8>> = HashMap::new();
+    let mut mismatches = 0;
+
+    for record in records {
+        if matches!(user_filter, Some(id) if id != record.user_id) {
+            continue;
+        }
+
+        let arrow = match record.direction {
+            Direction::Inbound => "->",
+            Direction::Outbound => "<-",
+        };
+
+        println!(
+            "[+{}ms] {} user {} ({} bytes)",
+            record.timestamp_ms.saturating_sub(start),
+            arrow,
+            record.user_id,
+            record.data.len()
+        );
+
+        let user = match players.get_id(record.user_id) {
+            Some(user) => user,
+            _ => {
+                let username = format!("replay {}", record.user_id);
+                players.add_player(User::stub(record.user_id, &username));
+                players.get_id(record.user_id).unwrap()
+            }
+        };
+
+        match record.direction {
+            Direction::Inbound => {
+                let mut player = user.write().await;
+                bancho::dispatch(&mut player, record.data).await;
+
+                let response = player.dequeue().await;
+                replayed.insert(record.user_id, response.to_vec());
+            }
+            Direction::Outbound => {
+                // responses to requests we didn't see (e.g. the capture started mid-session) are skipped
+                if let Some(response) = replayed.remove(&record.user_id) {
+                    mismatches += compare(&record.data, &response);
+                }
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        println!("{} packets differ from the capture", mismatches);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+
+use crate::packets::codec::BanchoEncode;
+use crate::packets::reader::{DecodeError, Reader};
+
+// On-disk format for session captures. each record is
+// [direction u8][timestamp ms u64][user id i32][length u32][bytes],
+// so a capture file is just records appended back to back.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum Direction {
+    Inbound = 0,  // request body from the client
+    Outbound = 1, // response body from dequeue
+}
+
+// Everything that can be wrong with a capture file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CaptureError {
+    Decode(DecodeError), // a record was cut short
+    UnknownDirection(u8),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(e) => write!(f, "{}", e),
+            Self::UnknownDirection(direction) => {
+                write!(f, "unknown capture direction {:#04x}", direction)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<DecodeError> for CaptureError {
+    fn from(e: DecodeError) -> Self {
+        return Self::Decode(e);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureRecord {
+    pub direction: Direction,
+    pub timestamp_ms: u64, // unix time
+    pub user_id: i32,
+    pub data: Vec<u8>,
+}
+
+impl CaptureRecord {
+    pub fn serialise(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.data.len() + 17);
+
+        (self.direction as u8).encode(&mut buf);
+        self.timestamp_ms.encode(&mut buf);
+        self.user_id.encode(&mut buf);
+        (self.data.len() as u32).encode(&mut buf);
+        buf.extend_from_slice(&self.data);
+
+        return buf;
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, CaptureError> {
+        let direction = match reader.read::<u8>()? {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            direction => return Err(CaptureError::UnknownDirection(direction)),
+        };
+        let timestamp_ms = reader.read()?;
+        let user_id = reader.read()?;
+        let len = reader.read::<u32>()?;
+
+        return Ok(Self {
+            direction: direction,
+            timestamp_ms: timestamp_ms,
+            user_id: user_id,
+            data: reader.read_packet(len as usize)?.read_raw()?,
+        });
+    }
+
+    /// Reads every record out of a capture file's contents.
+    pub fn read_all(bytes: Vec<u8>) -> Result<Vec<Self>, CaptureError> {
+        let mut reader = Reader::new(bytes);
+        let mut records = Vec::new();
+
+        while !reader.empty() {
+            records.push(Self::read(&mut reader)?);
+        }
+
+        return Ok(records);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(direction: Direction) -> CaptureRecord {
+        return CaptureRecord {
+            direction: direction,
+            timestamp_ms: 1_650_000_000_000,
+            user_id: 1000,
+            data: vec![4, 0, 0, 0, 0, 0, 0],
+        };
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = vec![record(Direction::Inbound), record(Direction::Outbound)];
+        let bytes: Vec<u8> = records.iter().flat_map(|r| r.serialise()).collect();
+
+        assert_eq!(CaptureRecord::read_all(bytes), Ok(records));
+    }
+
+    #[test]
+    fn unknown_direction_is_an_error() {
+        let mut bytes = record(Direction::Outbound).serialise();
+        bytes[0] = 2;
+
+        assert_eq!(
+            CaptureRecord::read_all(bytes),
+            Err(CaptureError::UnknownDirection(2))
+        );
+    }
+
+    #[test]
+    fn cut_off_records_are_an_error() {
+        let mut bytes = record(Direction::Inbound).serialise();
+        bytes.pop();
+
+        assert!(matches!(
+            CaptureRecord::read_all(bytes),
+            Err(CaptureError::Decode(DecodeError::Truncated { .. }))
+        ));
+    }
+}
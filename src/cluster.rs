@@ -0,0 +1,980 @@
+use futures::StreamExt;
+use ntex::util::Bytes;
+use num_traits::FromPrimitive;
+use once_cell::sync::OnceCell;
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, RedisResult};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::anticheat;
+use crate::constants::action::Action;
+use crate::constants::mode::Mode;
+use crate::constants::presence::PresenceFilter;
+use crate::constants::privileges::BanchoPrivileges;
+use crate::objects::mods::Mods;
+use crate::objects::spectating;
+use crate::objects::user::User;
+use crate::objects::writes::unix_time;
+use crate::packets::codec::{BanchoDecode, BanchoEncode};
+use crate::packets::handlers;
+use crate::packets::reader::{DecodeResult, Reader};
+use crate::players;
+
+// Running several rosu instances behind one load balancer. sessions are stored in redis
+// by token so any instance can pick up a request for one, and packets for users on other
+// instances are published to every instance, which delivers them to whoever it's serving.
+//
+// rosu:session:<token>   hash of what's needed to restore the session
+// rosu:presence:<id>     the user's presence packet, for instances that don't have them
+// rosu:stats:<id>        the user's stats packet, likewise
+// rosu:online            sorted set of visible user ids, scored by when they were last refreshed
+
+const SESSION_TTL: usize = 300; // seconds, refreshed while the session is alive
+const PACKETS_CHANNEL: &str = "rosu:packets";
+const ONLINE_KEY: &str = "rosu:online";
+
+static connection: OnceCell<MultiplexedConnection> = OnceCell::new();
+static instance_id: OnceCell<String> = OnceCell::new();
+
+// Something another instance has to act on, published to PACKETS_CHANNEL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    // to everyone online
+    Broadcast {
+        data: Vec<u8>,
+    },
+    // to everyone whose presence filter lets through updates about the user
+    Presence {
+        user_id: i32,
+        data: Vec<u8>,
+    },
+    // to specific users
+    Deliver {
+        user_ids: Vec<i32>,
+        data: Vec<u8>,
+    },
+    // spectating a host on another instance
+    StartSpectating {
+        host_id: i32,
+        spectator_id: i32,
+    },
+    StopSpectating {
+        host_id: i32,
+        spectator_id: i32,
+    },
+    CantSpectate {
+        host_id: i32,
+        spectator_id: i32,
+    },
+    // the host logged out, their spectators here are left without one
+    HostLeft {
+        host_id: i32,
+        spectators: Vec<i32>,
+    },
+    // the user logged in again, any older session of theirs is stale
+    Relogged {
+        user_id: i32,
+        token: String, // the new session's
+    },
+    // another instance is serving this session now, whoever had it hands it over
+    Takeover {
+        user_id: i32,
+        token: String,
+    },
+    // what the instance that had the session was holding for it
+    Handoff {
+        user_id: i32,
+        host_id: i32, // 0 if they weren't spectating
+        spectators: Vec<i32>,
+        data: Vec<u8>, // packets they hadn't polled for yet
+    },
+    // to every staff member, e.g. anticheat flags
+    StaffNotice {
+        data: Vec<u8>,
+    },
+}
+
+impl Event {
+    pub fn encode(&self, instance: &str, buf: &mut Vec<u8>) {
+        instance.encode(buf);
+
+        match self {
+            Event::Broadcast { data } => {
+                0_u8.encode(buf);
+                buf.extend_from_slice(data);
+            }
+            Event::Presence { user_id, data } => {
+                1_u8.encode(buf);
+                user_id.encode(buf);
+                buf.extend_from_slice(data);
+            }
+            Event::Deliver { user_ids, data } => {
+                2_u8.encode(buf);
+                user_ids.encode(buf);
+                buf.extend_from_slice(data);
+            }
+            Event::StartSpectating {
+                host_id,
+                spectator_id,
+            } => {
+                3_u8.encode(buf);
+                host_id.encode(buf);
+                spectator_id.encode(buf);
+            }
+            Event::StopSpectating {
+                host_id,
+                spectator_id,
+            } => {
+                4_u8.encode(buf);
+                host_id.encode(buf);
+                spectator_id.encode(buf);
+            }
+            Event::CantSpectate {
+                host_id,
+                spectator_id,
+            } => {
+                5_u8.encode(buf);
+                host_id.encode(buf);
+                spectator_id.encode(buf);
+            }
+            Event::HostLeft {
+                host_id,
+                spectators,
+            } => {
+                6_u8.encode(buf);
+                host_id.encode(buf);
+                spectators.encode(buf);
+            }
+            Event::Relogged { user_id, token } => {
+                7_u8.encode(buf);
+                user_id.encode(buf);
+                token.encode(buf);
+            }
+            Event::Takeover { user_id, token } => {
+                8_u8.encode(buf);
+                user_id.encode(buf);
+                token.encode(buf);
+            }
+            Event::Handoff {
+                user_id,
+                host_id,
+                spectators,
+                data,
+            } => {
+                9_u8.encode(buf);
+                user_id.encode(buf);
+                host_id.encode(buf);
+                spectators.encode(buf);
+                buf.extend_from_slice(data);
+            }
+            Event::StaffNotice { data } => {
+                10_u8.encode(buf);
+                buf.extend_from_slice(data);
+            }
+        }
+    }
+
+    // Returns the instance that sent the event along with it, `None` for unknown events.
+    pub fn decode(reader: &mut Reader) -> DecodeResult<(String, Option<Self>)> {
+        let instance = String::decode(reader)?;
+
+        let event = match reader.read::<u8>()? {
+            0 => Event::Broadcast {
+                data: reader.read_raw()?,
+            },
+            1 => Event::Presence {
+                user_id: reader.read()?,
+                data: reader.read_raw()?,
+            },
+            2 => Event::Deliver {
+                user_ids: reader.read()?,
+                data: reader.read_raw()?,
+            },
+            3 => Event::StartSpectating {
+                host_id: reader.read()?,
+                spectator_id: reader.read()?,
+            },
+            4 => Event::StopSpectating {
+                host_id: reader.read()?,
+                spectator_id: reader.read()?,
+            },
+            5 => Event::CantSpectate {
+                host_id: reader.read()?,
+                spectator_id: reader.read()?,
+            },
+            6 => Event::HostLeft {
+                host_id: reader.read()?,
+                spectators: reader.read()?,
+            },
+            7 => Event::Relogged {
+                user_id: reader.read()?,
+                token: reader.read()?,
+            },
+            8 => Event::Takeover {
+                user_id: reader.read()?,
+                token: reader.read()?,
+            },
+            9 => Event::Handoff {
+                user_id: reader.read()?,
+                host_id: reader.read()?,
+                spectators: reader.read()?,
+                data: reader.read_raw()?,
+            },
+            10 => Event::StaffNotice {
+                data: reader.read_raw()?,
+            },
+            _ => return Ok((instance, None)),
+        };
+
+        return Ok((instance, Some(event)));
+    }
+}
+
+// What's needed to restore a session on another instance, the rest comes from the database.
+// channels aren't in here, nothing joins them until chat is implemented.
+pub struct Session {
+    pub user_id: i32,
+    pub username: String,
+    pub osu_ver: String,
+    pub utc_offset: i32,
+    pub osu_md5: String,
+    pub bancho_priv: i32,
+    pub long: f32,
+    pub lat: f32,
+    pub filter: u8,
+    // their status, so the users on other instances see the same thing after a takeover
+    pub action: u8,
+    pub info_text: String,
+    pub map_md5: String,
+    pub mods: i32,
+    pub mode: i32, // vanilla mode, relax and autopilot come from the mods
+    pub map_id: i32,
+}
+
+impl Session {
+    fn from_user(user: &User) -> Self {
+        return Self {
+            user_id: user.id,
+            username: user.username.clone(),
+            osu_ver: user.osuver.clone(),
+            utc_offset: user.utc_offset,
+            osu_md5: user.osu_md5.clone(),
+            bancho_priv: user.bancho_priv.value(),
+            long: user.long,
+            lat: user.lat,
+            filter: user.presence.filter() as u8,
+            action: user.action as u8,
+            info_text: user.info_text.clone(),
+            map_md5: user.map_md5.clone(),
+            mods: user.mods.bits(),
+            mode: user.current_mode.as_vn(),
+            map_id: user.map_id,
+        };
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        return vec![
+            ("user_id", self.user_id.to_string()),
+            ("username", self.username.clone()),
+            ("osu_ver", self.osu_ver.clone()),
+            ("utc_offset", self.utc_offset.to_string()),
+            ("osu_md5", self.osu_md5.clone()),
+            ("bancho_priv", self.bancho_priv.to_string()),
+            ("long", self.long.to_string()),
+            ("lat", self.lat.to_string()),
+            ("filter", self.filter.to_string()),
+            ("action", self.action.to_string()),
+            ("info_text", self.info_text.clone()),
+            ("map_md5", self.map_md5.clone()),
+            ("mods", self.mods.to_string()),
+            ("mode", self.mode.to_string()),
+            ("map_id", self.map_id.to_string()),
+        ];
+    }
+
+    fn from_fields(fields: &HashMap<String, String>) -> Option<Self> {
+        return Some(Self {
+            user_id: fields.get("user_id")?.parse().ok()?,
+            username: fields.get("username")?.clone(),
+            osu_ver: fields.get("osu_ver")?.clone(),
+            utc_offset: fields.get("utc_offset")?.parse().ok()?,
+            osu_md5: fields.get("osu_md5")?.clone(),
+            bancho_priv: fields.get("bancho_priv")?.parse().ok()?,
+            long: fields.get("long")?.parse().ok()?,
+            lat: fields.get("lat")?.parse().ok()?,
+            filter: fields.get("filter")?.parse().ok()?,
+            action: fields.get("action")?.parse().ok()?,
+            info_text: fields.get("info_text")?.clone(),
+            map_md5: fields.get("map_md5")?.clone(),
+            mods: fields.get("mods")?.parse().ok()?,
+            mode: fields.get("mode")?.parse().ok()?,
+            map_id: fields.get("map_id")?.parse().ok()?,
+        });
+    }
+
+    // Puts what the session was holding back onto a freshly loaded user.
+    fn restore(self, user: &mut User) {
+        user.osu_md5 = self.osu_md5;
+        user.bancho_priv = BanchoPrivileges::from_value(self.bancho_priv as i64);
+        user.long = self.long;
+        user.lat = self.lat;
+
+        if let Some(filter) = PresenceFilter::from_u8(self.filter) {
+            user.presence.set_filter(filter);
+        }
+
+        user.action = Action::from_u8(self.action).unwrap_or(Action::Unknown);
+        user.info_text = self.info_text;
+        user.map_md5 = self.map_md5;
+        user.mods = Mods::from_value(self.mods);
+        user.current_mode = Mode::from_mods(self.mode, self.mods);
+        user.map_id = self.map_id;
+    }
+}
+
+#[inline(always)]
+fn session_key(token: &str) -> String {
+    return format!("rosu:session:{}", token);
+}
+
+#[inline(always)]
+fn presence_key(user_id: i32) -> String {
+    return format!("rosu:presence:{}", user_id);
+}
+
+#[inline(always)]
+fn stats_key(user_id: i32) -> String {
+    return format!("rosu:stats:{}", user_id);
+}
+
+// Whether this instance is running as part of a cluster.
+pub fn enabled() -> bool {
+    return connection.get().is_some();
+}
+
+#[inline(always)]
+fn conn() -> MultiplexedConnection {
+    return connection.get().unwrap().clone();
+}
+
+fn instance() -> &'static str {
+    return instance_id.get().map(|id| id.as_str()).unwrap_or("");
+}
+
+fn log_error<T>(result: RedisResult<T>, action: &'static str) -> Option<T> {
+    return match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!(error = %e, "failed to {}", action);
+            None
+        }
+    };
+}
+
+pub async fn init() -> RedisResult<()> {
+    let conn = crate::redis
+        .get()
+        .unwrap()
+        .get_multiplexed_tokio_connection()
+        .await?;
+
+    instance_id.set(Uuid::new_v4().to_string()).unwrap();
+    connection.set(conn).ok();
+
+    info!(instance = instance(), "running as part of a cluster");
+    return Ok(());
+}
+
+async fn publish(event: Event) {
+    if !enabled() {
+        return;
+    }
+
+    let mut buf = Vec::new();
+    event.encode(instance(), &mut buf);
+
+    log_error(
+        conn().publish::<_, _, ()>(PACKETS_CHANNEL, buf).await,
+        "publish cluster event",
+    );
+}
+
+// Sends to everyone online, on every instance.
+pub async fn broadcast<B: Into<Bytes>>(bytes: B) {
+    let bytes = bytes.into();
+    players.enqueue(bytes.clone()).await;
+
+    publish(Event::Broadcast {
+        data: bytes.to_vec(),
+    })
+    .await;
+}
+
+// Sends an update about the user to everyone who wants it, on every instance.
+pub async fn broadcast_presence<B: Into<Bytes>>(user_id: i32, bytes: B) {
+    let bytes = bytes.into();
+    players.enqueue_presence(user_id, bytes.clone()).await;
+
+    publish(Event::Presence {
+        user_id: user_id,
+        data: bytes.to_vec(),
+    })
+    .await;
+}
+
+// Sends to the given users wherever they're being served.
+pub async fn enqueue_to<B: Into<Bytes>>(user_ids: &[i32], bytes: B) {
+    let bytes = bytes.into();
+    players.enqueue_to(user_ids, bytes.clone()).await;
+
+    if !enabled() {
+        return;
+    }
+
+    let remote = user_ids
+        .iter()
+        .copied()
+        .filter(|id| players.get_id(*id).is_none())
+        .collect::<Vec<i32>>();
+
+    if !remote.is_empty() {
+        publish(Event::Deliver {
+            user_ids: remote,
+            data: bytes.to_vec(),
+        })
+        .await;
+    }
+}
+
+// Hands an event to the other instances, returns false if there aren't any.
+pub async fn relay(event: Event) -> bool {
+    if !enabled() {
+        return false;
+    }
+
+    publish(event).await;
+    return true;
+}
+
+// Stores the session so other instances can restore it, and tells them
+// to drop any older session of the user's. called once they're logged in.
+pub async fn store_session(user: &User) {
+    if !enabled() {
+        return;
+    }
+
+    let session = Session::from_user(user);
+    let result = redis::pipe()
+        .atomic()
+        .hset_multiple(session_key(&user.token), &session.fields())
+        .ignore()
+        .expire(session_key(&user.token), SESSION_TTL)
+        .ignore()
+        .query_async::<_, ()>(&mut conn())
+        .await;
+
+    log_error(result, "store session");
+}
+
+// Keeps the presence and stats other instances hand out for the user up to date.
+pub async fn update_presence(user: &User) {
+    if !enabled() || user.restricted() {
+        return;
+    }
+
+    let result = redis::pipe()
+        .set_ex(
+            presence_key(user.id),
+            handlers::user_presence(user),
+            SESSION_TTL,
+        )
+        .ignore()
+        .set_ex(stats_key(user.id), handlers::user_stats(user), SESSION_TTL)
+        .ignore()
+        .zadd(ONLINE_KEY, user.id, unix_time())
+        .ignore()
+        .query_async::<_, ()>(&mut conn())
+        .await;
+
+    log_error(result, "update presence");
+}
+
+// Keeps the sessions being served here alive, given as (user id, token, visible).
+// takes what it needs rather than the users so the reaper doesn't hold their locks meanwhile.
+pub async fn refresh_sessions(sessions: &[(i32, String, bool)]) {
+    if !enabled() || sessions.is_empty() {
+        return;
+    }
+
+    let mut pipe = redis::pipe();
+    for (user_id, token, visible) in sessions {
+        pipe.expire(session_key(token), SESSION_TTL).ignore();
+
+        if *visible {
+            pipe.expire(presence_key(*user_id), SESSION_TTL)
+                .ignore()
+                .expire(stats_key(*user_id), SESSION_TTL)
+                .ignore()
+                .zadd(ONLINE_KEY, *user_id, unix_time())
+                .ignore();
+        }
+    }
+
+    log_error(
+        pipe.query_async::<_, ()>(&mut conn()).await,
+        "refresh sessions",
+    );
+}
+
+pub async fn remove_session(user: &User) {
+    if !enabled() {
+        return;
+    }
+
+    let result = redis::pipe()
+        .del(session_key(&user.token))
+        .ignore()
+        .del(presence_key(user.id))
+        .ignore()
+        .del(stats_key(user.id))
+        .ignore()
+        .zrem(ONLINE_KEY, user.id)
+        .ignore()
+        .query_async::<_, ()>(&mut conn())
+        .await;
+
+    log_error(result, "remove session");
+}
+
+// Visible users online on other instances.
+pub async fn remote_ids() -> Vec<i32> {
+    if !enabled() {
+        return Vec::new();
+    }
+
+    // sessions of instances that died without logging them out stop being refreshed
+    let since = unix_time() - SESSION_TTL as i32;
+    let ids: Vec<i32> = log_error(
+        conn().zrangebyscore(ONLINE_KEY, since, "+inf").await,
+        "fetch online users",
+    )
+    .unwrap_or_default();
+
+    return ids
+        .into_iter()
+        .filter(|id| players.get_id(*id).is_none())
+        .collect();
+}
+
+// Whether the user is visible and online on any instance.
+pub async fn is_online(user_id: i32) -> bool {
+    if !enabled() {
+        return false;
+    }
+
+    let since = unix_time() - SESSION_TTL as i32;
+    let score: Option<f64> = log_error(
+        conn().zscore(ONLINE_KEY, user_id).await,
+        "fetch online user",
+    )
+    .flatten();
+
+    return matches!(score, Some(score) if score >= since as f64);
+}
+
+async fn fetch_packets(keys: Vec<String>) -> Vec<u8> {
+    if !enabled() || keys.is_empty() {
+        return Vec::new();
+    }
+
+    let packets: Vec<Option<Vec<u8>>> = log_error(
+        redis::cmd("MGET").arg(keys).query_async(&mut conn()).await,
+        "fetch presences",
+    )
+    .unwrap_or_default();
+
+    return packets.into_iter().flatten().flatten().collect();
+}
+
+// Presence packets of users on other instances.
+pub async fn presences(user_ids: &[i32]) -> Vec<u8> {
+    return fetch_packets(user_ids.iter().map(|id| presence_key(*id)).collect()).await;
+}
+
+// Stats packets of users on other instances.
+pub async fn stats(user_ids: &[i32]) -> Vec<u8> {
+    return fetch_packets(user_ids.iter().map(|id| stats_key(*id)).collect()).await;
+}
+
+async fn load_session(token: &str) -> Option<Session> {
+    if !enabled() {
+        return None;
+    }
+
+    let fields: HashMap<String, String> =
+        log_error(conn().hgetall(session_key(token)).await, "fetch session")?;
+
+    return Session::from_fields(&fields);
+}
+
+// Restores a session that was being served by another instance, which hands over
+// whatever it was holding for them once it hears about it.
+pub async fn take_over(token: &str) -> Option<Arc<RwLock<User>>> {
+    let session = load_session(token).await?;
+    let token_uuid = Uuid::parse_str(token).ok()?;
+
+    let mut user = User::from_sql(
+        &session.username,
+        token_uuid,
+        &session.osu_ver,
+        session.utc_offset,
+    )
+    .await?;
+
+    session.restore(&mut user);
+
+    let user_id = user.id;
+    players.add_player(user);
+
+    publish(Event::Takeover {
+        user_id: user_id,
+        token: token.to_string(),
+    })
+    .await;
+
+    info!(user_id, "took over session");
+    return players.get_token(token);
+}
+
+// Hands over a session another instance has taken over, if we have it.
+async fn hand_off(user_id: i32, token: &str) {
+    let u = match players.get_token(token) {
+        Some(u) => u,
+        _ => return,
+    };
+
+    players.remove(user_id, token);
+
+    let user = u.read().await;
+    let spectators = user.spectating.take_spectators();
+    let host_id = user.spectating.set_host(None).unwrap_or(0);
+
+    // the spectators' instances still point at this user, so they carry on receiving frames
+    publish(Event::Handoff {
+        user_id: user_id,
+        host_id: host_id,
+        spectators: spectators,
+        data: user.dequeue().await.to_vec(),
+    })
+    .await;
+
+    info!(user_id, "handed off session");
+}
+
+// Logs out an older session of a user who logged in again, here or on another instance.
+// everyone else already heard about the new session, so they aren't told the user went
+// offline, and only the old session is removed from redis.
+pub async fn drop_stale_session(user_id: i32, token: &str) {
+    let u = match players.get_id(user_id) {
+        Some(u) => u,
+        _ => return,
+    };
+
+    let mut user = u.write().await;
+    if user.token == token {
+        return;
+    }
+
+    // their spectators' clients are stopped, everyone else carries on with the new session
+    let spectators = user.end_session().await;
+    enqueue_to(&spectators, handlers::logout(user_id)).await;
+
+    if enabled() {
+        log_error(
+            conn().del::<_, ()>(session_key(&user.token)).await,
+            "remove stale session",
+        );
+    }
+
+    info!(user_id, "dropped session after a relogin");
+}
+
+async fn handle(event: Event) {
+    match event {
+        Event::Broadcast { data } => players.enqueue(data).await,
+        Event::Presence { user_id, data } => players.enqueue_presence(user_id, data).await,
+        Event::Deliver { user_ids, data } => players.enqueue_to(&user_ids, data).await,
+        // these do nothing on the instances that aren't serving the host
+        Event::StartSpectating {
+            host_id,
+            spectator_id,
+        } => {
+            spectating::join(host_id, spectator_id).await;
+        }
+        Event::StopSpectating {
+            host_id,
+            spectator_id,
+        } => spectating::leave(host_id, spectator_id).await,
+        Event::CantSpectate {
+            host_id,
+            spectator_id,
+        } => spectating::cant_spectate(host_id, spectator_id).await,
+        Event::HostLeft {
+            host_id,
+            spectators,
+        } => {
+            for spectator_id in spectators {
+                if let Some(spectator) = players.spectating(spectator_id) {
+                    spectator.clear_host(host_id);
+                }
+            }
+        }
+        Event::Relogged { user_id, token } => {
+            // logging them out locks them, which would hold up every other event
+            tokio::spawn(async move {
+                drop_stale_session(user_id, &token).await;
+            });
+        }
+        Event::Takeover { user_id, token } => hand_off(user_id, &token).await,
+        Event::Handoff {
+            user_id,
+            host_id,
+            spectators,
+            data,
+        } => {
+            let state = match players.spectating(user_id) {
+                Some(state) => state,
+                _ => return,
+            };
+
+            for spectator_id in spectators {
+                state.add_spectator(spectator_id);
+            }
+
+            if host_id != 0 {
+                state.set_host(Some(host_id));
+            }
+
+            players.enqueue_to(&[user_id], data).await;
+        }
+        Event::StaffNotice { data } => {
+            // it locks users, which would hold up every other event
+            tokio::spawn(async move {
+                anticheat::notify_local_staff(data).await;
+            });
+        }
+    }
+}
+
+// Acts on events published by the other instances.
+pub async fn listen() {
+    let conn = crate::redis
+        .get()
+        .unwrap()
+        .get_async_connection()
+        .await
+        .unwrap();
+    let mut pubsub_conn = conn.into_pubsub();
+    pubsub_conn.subscribe(PACKETS_CHANNEL).await.unwrap();
+
+    let mut messages = pubsub_conn.on_message();
+    while let Some(msg) = messages.next().await {
+        let mut reader = Reader::new(msg.get_payload_bytes().to_vec());
+
+        match Event::decode(&mut reader) {
+            Ok((sender, _)) if sender == instance() => continue,
+            Ok((_, Some(event))) => handle(event).await,
+            Ok((sender, None)) => warn!(%sender, "unknown cluster event"),
+            Err(e) => warn!(error = %e, "failed to decode cluster event"),
+        }
+    }
+
+    error!("cluster event stream ended");
+}
+
+// Announces a fresh login, so other instances drop any older session of theirs.
+pub async fn logged_in(user: &User) {
+    store_session(user).await;
+    update_presence(user).await;
+
+    publish(Event::Relogged {
+        user_id: user.id,
+        token: user.token.clone(),
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(event: Event) {
+        let mut buf = Vec::new();
+        event.encode("instance", &mut buf);
+
+        let mut reader = Reader::new(buf);
+        assert_eq!(
+            Event::decode(&mut reader).unwrap(),
+            ("instance".to_string(), Some(event))
+        );
+        assert!(reader.empty());
+    }
+
+    #[test]
+    fn events_round_trip() {
+        round_trip(Event::Broadcast {
+            data: vec![1, 2, 3],
+        });
+        round_trip(Event::Presence {
+            user_id: 1000,
+            data: vec![4, 5],
+        });
+        round_trip(Event::Deliver {
+            user_ids: vec![1000, 1001],
+            data: vec![6],
+        });
+        round_trip(Event::StartSpectating {
+            host_id: 1000,
+            spectator_id: 1001,
+        });
+        round_trip(Event::StopSpectating {
+            host_id: 1000,
+            spectator_id: 1001,
+        });
+        round_trip(Event::CantSpectate {
+            host_id: 1000,
+            spectator_id: 1001,
+        });
+        round_trip(Event::HostLeft {
+            host_id: 1000,
+            spectators: vec![1001],
+        });
+        round_trip(Event::Relogged {
+            user_id: 1000,
+            token: "9c5c7b5e-0a1b-4a8e-8a4c-2f1f0b6b2d53".to_string(),
+        });
+        round_trip(Event::Takeover {
+            user_id: 1000,
+            token: "9c5c7b5e-0a1b-4a8e-8a4c-2f1f0b6b2d53".to_string(),
+        });
+        round_trip(Event::Handoff {
+            user_id: 1000,
+            host_id: 0,
+            spectators: vec![1001, 1002],
+            data: Vec::new(),
+        });
+        round_trip(Event::StaffNotice { data: vec![7, 8] });
+    }
+
+    async fn next_event(messages: &mut (impl futures::Stream<Item = redis::Msg> + Unpin)) -> Event {
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), messages.next())
+            .await
+            .expect("no event was published")
+            .unwrap();
+
+        let mut reader = Reader::new(msg.get_payload_bytes().to_vec());
+        return Event::decode(&mut reader).unwrap().1.unwrap();
+    }
+
+    // needs a redis server, run with `cargo test -- --ignored`. ROSU_TEST_REDIS points it
+    // somewhere other than a local one. the other instance is played by the test, which
+    // sees whatever this one publishes.
+    #[tokio::test]
+    #[ignore]
+    async fn sessions_move_between_instances() {
+        let url = std::env::var("ROSU_TEST_REDIS").unwrap_or("redis://127.0.0.1/".to_string());
+        crate::redis.get_or_init(|| redis::Client::open(url.as_str()).unwrap());
+        init().await.unwrap();
+
+        let mut pubsub_conn = crate::redis
+            .get()
+            .unwrap()
+            .get_async_connection()
+            .await
+            .unwrap()
+            .into_pubsub();
+        pubsub_conn.subscribe(PACKETS_CHANNEL).await.unwrap();
+        let mut messages = pubsub_conn.on_message();
+
+        // the instance serving them keeps their session up to date
+        let mut user = User::stub(9101, "mover");
+        user.action = Action::Playing;
+        user.info_text = "Camellia - Exit This Earth's Atomosphere".to_string();
+        user.map_md5 = "0123456789abcdef0123456789abcdef".to_string();
+        user.mods = Mods::HIDDEN | Mods::RELAX;
+        user.current_mode = Mode::std_rx;
+        user.map_id = 75;
+        user.presence.set_filter(PresenceFilter::Friends);
+
+        store_session(&user).await;
+        update_presence(&user).await;
+        assert!(is_online(9101).await);
+
+        // another instance restores it onto the user it loads
+        let mut restored = User::stub(9101, "mover");
+        load_session(&user.token)
+            .await
+            .unwrap()
+            .restore(&mut restored);
+
+        assert_eq!(restored.action, user.action);
+        assert_eq!(restored.info_text, user.info_text);
+        assert_eq!(restored.map_md5, user.map_md5);
+        assert_eq!(restored.mods, user.mods);
+        assert_eq!(restored.current_mode, user.current_mode);
+        assert_eq!(restored.map_id, user.map_id);
+        assert_eq!(restored.presence.filter(), PresenceFilter::Friends);
+
+        // and this one hands over what it was still holding for them
+        let token = user.token.clone();
+        user.spectating.add_spectator(9103);
+        user.enqueue(vec![1, 2, 3]).await;
+        players.add_player(user);
+
+        hand_off(9101, &token).await;
+        assert!(players.get_id(9101).is_none());
+        assert_eq!(
+            next_event(&mut messages).await,
+            Event::Handoff {
+                user_id: 9101,
+                host_id: 0,
+                spectators: vec![9103],
+                data: vec![1, 2, 3],
+            }
+        );
+
+        // packets for someone who isn't here go to whichever instance is serving them
+        enqueue_to(&[9102], vec![4, 5]).await;
+        assert_eq!(
+            next_event(&mut messages).await,
+            Event::Deliver {
+                user_ids: vec![9102],
+                data: vec![4, 5],
+            }
+        );
+
+        let cleanup = redis::pipe()
+            .del(session_key(&token))
+            .del(presence_key(9101))
+            .del(stats_key(9101))
+            .zrem(ONLINE_KEY, 9101)
+            .query_async::<_, ()>(&mut conn())
+            .await;
+        assert!(cleanup.is_ok());
+    }
+
+    #[test]
+    fn unknown_events_are_skipped() {
+        let mut buf = Vec::new();
+        "instance".encode(&mut buf);
+        255_u8.encode(&mut buf);
+
+        let (sender, event) = Event::decode(&mut Reader::new(buf)).unwrap();
+        assert_eq!(sender, "instance");
+        assert_eq!(event, None);
+    }
+}
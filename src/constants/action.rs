@@ -1,7 +1,7 @@
 use num_derive::FromPrimitive;
-use serde::{Serialize, Serializer};
+use strum_macros::IntoStaticStr;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive, IntoStaticStr)]
 #[repr(u8)]
 pub enum Action {
     Idle = 0,
@@ -19,12 +19,3 @@ pub enum Action {
     Multiplaying = 12,
     OsuDirect = 13,
 }
-
-impl Serialize for Action {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_u8(*self as u8)
-    }
-}
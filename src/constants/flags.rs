@@ -0,0 +1,22 @@
+use bitflags::bitflags;
+
+bitflags! {
+    // the client's own anticheat flags, stored as `flag_enum` in lastfm_flags.
+    // server side detections reuse the closest one so they show up alongside them.
+    pub struct LastFmFlags: i32 {
+        const CLEAN                     = 0;
+        const SPEED_HACK_DETECTED       = 1 << 1;
+        const INCORRECT_MOD_VALUE       = 1 << 2;
+        const MULTIPLE_OSU_CLIENTS      = 1 << 3;
+        const CHECKSUM_FAILURE          = 1 << 4;
+        const FLASHLIGHT_CHECKSUM       = 1 << 5;
+        const OSU_EXECUTABLE_CHECKSUM   = 1 << 6;
+        const MISSING_PROCESSES_IN_LIST = 1 << 7;
+        const FLASHLIGHT_IMAGE_HACK     = 1 << 8;
+        const SPINNER_HACK              = 1 << 9;
+        const TRANSPARENT_WINDOW        = 1 << 10;
+        const FAST_PRESS                = 1 << 11;
+        const RAW_MOUSE_DISCREPANCY     = 1 << 12;
+        const RAW_KEYBOARD_DISCREPANCY  = 1 << 13;
+    }
+}
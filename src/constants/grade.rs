@@ -0,0 +1,234 @@
+use crate::objects::mods::Mods;
+
+// the mods that turn SS and S silver
+const SILVER_MODS: i32 = Mods::HIDDEN.bits() | Mods::FLASHLIGHT.bits() | Mods::FADEIN.bits();
+
+// grades as the client numbers them, N means no score
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Grade {
+    XH = 0, // silver SS
+    SH = 1, // silver S
+    X = 2,  // SS
+    S = 3,
+    A = 4,
+    B = 5,
+    C = 6,
+    D = 7,
+    F = 8,
+    N = 9,
+}
+
+// hit counts of a score, named like the scores table columns
+pub struct Hits {
+    pub n300: i32,
+    pub n100: i32,
+    pub n50: i32,
+    pub geki: i32,
+    pub katu: i32,
+    pub miss: i32,
+}
+
+impl Grade {
+    // Works out the grade the client would show for a score, `mode` is the vanilla mode (0-3)
+    // and `mods` the mods' bits.
+    pub fn from_hits(mode: i32, mods: i32, hits: &Hits) -> Self {
+        let grade = match mode {
+            0 | 1 => {
+                // taiko has no 50s
+                let n50 = if mode == 1 { 0 } else { hits.n50 };
+                let total = (hits.n300 + hits.n100 + n50 + hits.miss) as f32;
+                if total <= 0.0 {
+                    return Grade::N;
+                }
+
+                let ratio300 = hits.n300 as f32 / total;
+                let ratio50 = n50 as f32 / total;
+
+                if ratio300 == 1.0 {
+                    Grade::X
+                } else if ratio300 > 0.9 && ratio50 <= 0.01 && hits.miss == 0 {
+                    Grade::S
+                } else if (ratio300 > 0.8 && hits.miss == 0) || ratio300 > 0.9 {
+                    Grade::A
+                } else if (ratio300 > 0.7 && hits.miss == 0) || ratio300 > 0.8 {
+                    Grade::B
+                } else if ratio300 > 0.6 {
+                    Grade::C
+                } else {
+                    Grade::D
+                }
+            }
+            2 => {
+                // fruits, drops and droplets caught out of everything, katu are missed droplets
+                let caught = (hits.n300 + hits.n100 + hits.n50) as f32;
+                let total = caught + (hits.katu + hits.miss) as f32;
+                if total <= 0.0 {
+                    return Grade::N;
+                }
+
+                let acc = caught / total;
+                if acc == 1.0 {
+                    Grade::X
+                } else if acc > 0.98 {
+                    Grade::S
+                } else if acc > 0.94 {
+                    Grade::A
+                } else if acc > 0.9 {
+                    Grade::B
+                } else if acc > 0.85 {
+                    Grade::C
+                } else {
+                    Grade::D
+                }
+            }
+            _ => {
+                let total = hits.geki + hits.n300 + hits.katu + hits.n100 + hits.n50 + hits.miss;
+                if total <= 0 {
+                    return Grade::N;
+                }
+
+                let points = (hits.geki + hits.n300) * 300
+                    + hits.katu * 200
+                    + hits.n100 * 100
+                    + hits.n50 * 50;
+                let acc = points as f32 / (total * 300) as f32;
+
+                if acc == 1.0 {
+                    Grade::X
+                } else if acc > 0.95 {
+                    Grade::S
+                } else if acc > 0.9 {
+                    Grade::A
+                } else if acc > 0.8 {
+                    Grade::B
+                } else if acc > 0.7 {
+                    Grade::C
+                } else {
+                    Grade::D
+                }
+            }
+        };
+
+        if mods & SILVER_MODS != 0 {
+            return match grade {
+                Grade::X => Grade::XH,
+                Grade::S => Grade::SH,
+                grade => grade,
+            };
+        }
+
+        return grade;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOMOD: i32 = 0;
+    const HIDDEN: i32 = 1 << 3;
+    const FLASHLIGHT: i32 = 1 << 10;
+    const FADEIN: i32 = 1 << 20;
+
+    fn hits(n300: i32, n100: i32, n50: i32, geki: i32, katu: i32, miss: i32) -> Hits {
+        return Hits {
+            n300: n300,
+            n100: n100,
+            n50: n50,
+            geki: geki,
+            katu: katu,
+            miss: miss,
+        };
+    }
+
+    #[test]
+    fn std_grades() {
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(100, 0, 0, 0, 0, 0)),
+            Grade::X
+        );
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(95, 5, 0, 0, 0, 0)),
+            Grade::S
+        );
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(95, 4, 0, 0, 0, 1)),
+            Grade::A
+        );
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(93, 5, 2, 0, 0, 0)),
+            Grade::A
+        );
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(75, 25, 0, 0, 0, 0)),
+            Grade::B
+        );
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(65, 30, 0, 0, 0, 5)),
+            Grade::C
+        );
+        assert_eq!(
+            Grade::from_hits(0, NOMOD, &hits(50, 50, 0, 0, 0, 0)),
+            Grade::D
+        );
+    }
+
+    #[test]
+    fn silver_grades() {
+        assert_eq!(
+            Grade::from_hits(0, HIDDEN, &hits(100, 0, 0, 0, 0, 0)),
+            Grade::XH
+        );
+        assert_eq!(
+            Grade::from_hits(0, FLASHLIGHT, &hits(95, 5, 0, 0, 0, 0)),
+            Grade::SH
+        );
+        assert_eq!(
+            Grade::from_hits(3, FADEIN, &hits(0, 0, 0, 100, 0, 0)),
+            Grade::XH
+        );
+        assert_eq!(
+            Grade::from_hits(0, HIDDEN, &hits(75, 25, 0, 0, 0, 0)),
+            Grade::B
+        );
+    }
+
+    #[test]
+    fn taiko_ignores_50s() {
+        assert_eq!(
+            Grade::from_hits(1, NOMOD, &hits(100, 0, 5, 0, 0, 0)),
+            Grade::X
+        );
+    }
+
+    #[test]
+    fn catch_and_mania_grades() {
+        assert_eq!(
+            Grade::from_hits(2, NOMOD, &hits(90, 5, 4, 0, 1, 0)),
+            Grade::S
+        );
+        assert_eq!(
+            Grade::from_hits(2, NOMOD, &hits(80, 6, 0, 0, 10, 4)),
+            Grade::C
+        );
+        assert_eq!(
+            Grade::from_hits(3, NOMOD, &hits(50, 0, 0, 50, 0, 0)),
+            Grade::X
+        );
+        assert_eq!(
+            Grade::from_hits(3, NOMOD, &hits(40, 0, 0, 50, 10, 0)),
+            Grade::S
+        );
+    }
+
+    #[test]
+    fn empty_scores_have_no_grade() {
+        for mode in 0..4 {
+            assert_eq!(
+                Grade::from_hits(mode, NOMOD, &hits(0, 0, 0, 0, 0, 0)),
+                Grade::N
+            );
+        }
+    }
+}
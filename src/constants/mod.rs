@@ -1,5 +1,9 @@
 pub mod action;
 pub mod country;
+pub mod flags;
+pub mod grade;
 pub mod mode;
 pub mod packets;
+pub mod presence;
 pub mod privileges;
+pub mod replay;
@@ -1,7 +1,7 @@
 use num_enum::TryFromPrimitive;
-use strum_macros::EnumIter;
+use strum_macros::{EnumIter, IntoStaticStr};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive, EnumIter)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive, EnumIter, IntoStaticStr)]
 #[repr(i32)]
 pub enum Mode {
     std = 0,
@@ -34,6 +34,16 @@ impl Mode {
         }
     }
 
+    pub fn scores_table(&self) -> &'static str {
+        if RELAX_MODES.contains(self) {
+            return "scores_relax";
+        } else if VANILLA_MODES.contains(self) {
+            return "scores";
+        } else {
+            return "scores_ap";
+        }
+    }
+
     pub fn as_vn(self) -> i32 {
         if STD_MODES.contains(&self) {
             return 0;
@@ -58,6 +68,7 @@ impl Mode {
         }
     }
 
+    // the mode comes from the client, so anything out of range is treated as standard
     pub fn from_mods(mode: i32, mods: i32) -> Self {
         if mods & 128 > 0 {
             // 128 = relax
@@ -65,16 +76,16 @@ impl Mode {
                 return Self::mania;
             }
 
-            return unsafe { std::mem::transmute(mode + 4) };
+            return Self::try_from_primitive(mode + 4).unwrap_or(Self::std_rx);
         } else if mods & 8192 > 0 {
             // 8192 = autopilot
             if mode != 0 {
-                return unsafe { std::mem::transmute(mode) };
+                return Self::try_from_primitive(mode).unwrap_or(Self::std_ap);
             }
 
             return Self::std_ap;
         }
 
-        return unsafe { std::mem::transmute(mode) };
+        return Self::try_from_primitive(mode).unwrap_or(Self::std);
     }
 }
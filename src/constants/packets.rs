@@ -1,7 +1,7 @@
 use num_derive::FromPrimitive;
-use serde::{Serialize, Serializer};
+use strum_macros::IntoStaticStr;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive, Hash, IntoStaticStr)]
 #[repr(i16)]
 pub enum Packets {
     OSU_CHANGE_ACTION = 0,
@@ -113,12 +113,3 @@ pub enum Packets {
     OSU_TOURNAMENT_JOIN_MATCH_CHANNEL = 108,
     OSU_TOURNAMENT_LEAVE_MATCH_CHANNEL = 109,
 }
-
-impl Serialize for Packets {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_i16(*self as i16)
-    }
-}
@@ -0,0 +1,11 @@
+use num_derive::FromPrimitive;
+use strum_macros::IntoStaticStr;
+
+// who a client wants presence and stats updates about, sent with OSU_RECEIVE_UPDATES
+#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive, IntoStaticStr)]
+#[repr(u8)]
+pub enum PresenceFilter {
+    Nobody = 0,
+    All = 1,
+    Friends = 2,
+}
@@ -4,7 +4,7 @@ bitflags! {
     #[derive(sqlx::FromRow)]
     pub struct Privileges: i64 {
         const USER_PUBLIC               = 1;
-        const USER_NORMAL               = 2 << 0;
+        const USER_NORMAL               = 2;
         const USER_DONOR                = 2 << 1;
         const ADMIN_ACCESS_RAP          = 2 << 2;
         const ADMIN_MANAGE_USERS        = 2 << 3;
@@ -75,3 +75,23 @@ impl BanchoPrivileges {
         return self.bits();
     }
 }
+
+// privileges of api keys in the `tokens` table, same bits as ripple's api uses
+bitflags! {
+    pub struct TokenPrivileges: i32 {
+        const READ = 1 << 0;
+        const READ_CONFIDENTIAL = 1 << 1;
+        const WRITE = 1 << 2;
+        const MANAGE_BADGES = 1 << 3;
+        const BETA_KEYS = 1 << 4;
+        const MANAGE_SETTINGS = 1 << 5;
+        const VIEW_USER_ADVANCED = 1 << 6;
+        const MANAGE_USER = 1 << 7;
+        const MANAGE_ROLES = 1 << 8;
+        const MANAGE_API_KEYS = 1 << 9;
+        const BLOG = 1 << 10;
+        const API_META = 1 << 11;
+        const BEATMAP = 1 << 12;
+        const BANCHO = 1 << 13;
+    }
+}
@@ -0,0 +1,17 @@
+use num_derive::FromPrimitive;
+use strum_macros::IntoStaticStr;
+
+// what a spectated player was doing when they sent a frame bundle
+#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive, IntoStaticStr)]
+#[repr(u8)]
+pub enum ReplayAction {
+    Standard = 0,
+    NewSong = 1,
+    Skip = 2,
+    Completion = 3,
+    Fail = 4,
+    Pause = 5,
+    Unpause = 6,
+    SongSelect = 7,
+    WatchingOther = 8,
+}
@@ -0,0 +1,79 @@
+// the whole server, so the binary and the tooling around it (fuzz targets, rosu-replay,
+// rosu-dissect) share one module tree. the globals are only set up by the binary,
+// tooling that only needs the codec never touches them.
+
+// i didn't want to add these global allows, but some are unfixable cus rust isn't smart enough
+#![allow(non_upper_case_globals)]
+#![allow(unused_variables)]
+#![allow(non_camel_case_types)]
+#![allow(dead_code)]
+// explicit returns, `field: field` and `new()` without `Default` are just how rosu is written
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::new_without_default)]
+
+extern crate redis;
+
+pub mod anticheat;
+pub mod api;
+pub mod bancho;
+pub mod capture;
+pub mod cluster;
+pub mod config;
+pub mod constants;
+pub mod metrics;
+pub mod objects;
+pub mod packets;
+pub mod pubsubs;
+pub mod replay;
+pub mod tasks;
+
+use maxminddb::Reader as MaxmindReader;
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::{MySql, Pool};
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::anticheat::Pipeline;
+use crate::config::Config;
+use crate::objects::captures::CaptureList;
+use crate::objects::players::PlayerList;
+use crate::objects::ratelimit::RateLimiter;
+use crate::objects::recordings::RecordingList;
+use crate::objects::settings::BanchoSettings;
+use crate::objects::writes::WriteQueue;
+
+use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
+
+lazy_static! {
+    pub static ref players: PlayerList = PlayerList::new();
+    pub static ref bcrypt_cache: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+    pub static ref settings: RwLock<BanchoSettings> = RwLock::new(BanchoSettings::new());
+    pub static ref captures: CaptureList = CaptureList::new();
+    pub static ref recordings: RecordingList = RecordingList::new();
+    pub static ref anticheat_pipeline: Pipeline = Pipeline::with_defaults();
+    pub static ref pending_writes: WriteQueue = WriteQueue::new();
+    pub static ref error_reports: RateLimiter = RateLimiter::new(Duration::from_secs(300)); // one per user per 5 minutes
+}
+
+pub static config: OnceCell<Config> = OnceCell::new();
+pub static reader: OnceCell<MaxmindReader<Vec<u8>>> = OnceCell::new();
+pub static db: OnceCell<Pool<MySql>> = OnceCell::new();
+pub static redis: OnceCell<redis::Client> = OnceCell::new();
+pub static shutting_down: AtomicBool = AtomicBool::new(false);
+
+// Sets up the globals for running the dispatcher outside of the server (rosu-replay, the fuzz
+// targets): the default config, and a database pool pointing nowhere so handlers that need
+// mysql fail and log rather than panic. has to be called from inside a tokio runtime.
+pub fn init_standalone() {
+    config.get_or_init(Config::default);
+    db.get_or_init(|| {
+        return MySqlPoolOptions::new()
+            .connect_timeout(Duration::from_millis(100))
+            .connect_lazy("mysql://rosu@127.0.0.1:1/rosu")
+            .unwrap();
+    });
+}
@@ -1,52 +1,27 @@
-// i didn't want to add these global allows, but some are unfixable cus rust isn't smart enough
-#![allow(non_upper_case_globals)]
-#![allow(unused_variables)]
-#![allow(non_camel_case_types)]
-#![allow(dead_code)]
-
-extern crate redis;
-
-mod bancho;
-mod constants;
-mod objects;
-mod packets;
-mod pubsubs;
+#![allow(clippy::needless_return)] // same style as the lib
 
 use ntex::http::Method;
 use ntex::util::Bytes;
 use ntex::web::{self, middleware, App, HttpRequest, HttpResponse};
 use sqlx::mysql::MySqlPoolOptions;
-use sqlx::{MySql, Pool};
-use std::env;
+use std::process;
 
 use maxminddb::Reader as MaxmindReader;
-use std::collections::HashMap;
-use tokio::sync::Mutex;
-
-use crate::objects::players::PlayerList;
-
-use lazy_static::lazy_static;
-use once_cell::sync::OnceCell;
+use tracing::error;
 
-lazy_static! {
-    static ref players: PlayerList = PlayerList::new();
-    static ref reader: MaxmindReader<Vec<u8>> =
-        MaxmindReader::open_readfile("ext/geoloc.mmdb").unwrap();
-    static ref bcrypt_cache: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
-}
-
-static db: OnceCell<Pool<MySql>> = OnceCell::new();
-static redis: OnceCell<redis::Client> = OnceCell::new();
+use rosu::config::{Config, LogFormat};
+use rosu::{api, bancho, cluster, metrics, objects, pubsubs, tasks};
+use rosu::{config, db, reader, redis};
 
 async fn handle_conn(req: HttpRequest, _data: Bytes) -> HttpResponse {
-    match req.method() {
-        &Method::GET => {
+    match *req.method() {
+        Method::GET => {
             // GET request, render index
             return HttpResponse::Ok().body("rosu 2022™️");
         }
-        &Method::POST => {
+        Method::POST => {
             // POST request, should be login/packet update request
-            if req.headers().get("User-Agent").unwrap().to_str().unwrap() == "osu!" {
+            if bancho::is_osu_client(req.headers()) {
                 // it's osu!
                 return bancho::bancho(req, _data.to_vec()).await;
             } else {
@@ -62,26 +37,92 @@ async fn handle_conn(req: HttpRequest, _data: Bytes) -> HttpResponse {
 
 #[ntex::main]
 async fn main() -> std::io::Result<()> {
+    let cfg = Config::load().unwrap_or_else(|e| {
+        eprintln!("rosu: {}", e);
+        process::exit(1);
+    });
+
+    let subscriber = tracing_subscriber::fmt().with_env_filter(cfg.log_filter());
+    match cfg.log_format {
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    let geoloc = MaxmindReader::open_readfile(&cfg.geoip_path).unwrap_or_else(|e| {
+        error!(path = %cfg.geoip_path, error = %e, "failed to open geoip database");
+        process::exit(1);
+    });
+    // maxminddb's reader isn't Debug so it can't be unwrapped, this is the only place it's set
+    reader.set(geoloc).ok();
+
     let pool = MySqlPoolOptions::new()
-        .connect(env::var("DATABASE_URL").unwrap().as_str())
+        .max_connections(cfg.database_pool_size)
+        .connect(&cfg.database_url)
         .await
         .unwrap();
 
     db.set(pool).unwrap();
 
-    let r = redis::Client::open("redis://127.0.0.1/").unwrap();
+    objects::settings::reload_settings().await;
+
+    let r = redis::Client::open(cfg.redis_url.as_str()).unwrap();
     redis.set(r).unwrap();
 
+    if cfg.cluster {
+        cluster::init().await.unwrap_or_else(|e| {
+            error!(error = %e, "failed to connect to redis for clustering");
+            process::exit(1);
+        });
+
+        tokio::spawn(async move {
+            cluster::listen().await;
+        });
+    }
+
+    config.set(cfg).unwrap();
+
     tokio::spawn(async move {
         pubsubs::initialise_pubsubs().await;
     });
 
-    web::server(move || {
+    tokio::spawn(async move {
+        tasks::reap_idle_sessions().await;
+    });
+
+    tokio::spawn(async move {
+        tasks::flush_pending_writes().await;
+    });
+
+    let server = web::server(move || {
         App::new()
             .wrap(middleware::Logger::default())
             .service(web::resource("/").to(handle_conn))
     })
-    .bind_uds("/tmp/rosu.sock")?
-    .run()
-    .await
+    .disable_signals(); // we shut down ourselves, see tasks::shutdown_on_signal
+
+    let cfg = config.get().unwrap();
+    let server = match cfg.listen {
+        Some(addr) => server.bind(addr)?,
+        _ => server.bind_uds(&cfg.socket)?,
+    };
+
+    let server = server.run();
+
+    // kept alive until bancho stops
+    let _metrics_server = match cfg.metrics_listen {
+        Some(addr) => Some(metrics::serve(addr)?),
+        _ => None,
+    };
+
+    let _api_server = match cfg.api_listen {
+        Some(addr) => Some(api::serve(addr)?),
+        _ => None,
+    };
+
+    let handle = server.clone();
+    tokio::spawn(async move {
+        tasks::shutdown_on_signal(handle).await;
+    });
+
+    server.await
 }
@@ -0,0 +1,152 @@
+use lazy_static::lazy_static;
+use ntex::web::{self, App, HttpResponse};
+use prometheus::{
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter_vec,
+    register_int_gauge, register_int_gauge_vec, Encoder, Histogram, HistogramVec, IntCounterVec,
+    IntGauge, IntGaugeVec, TextEncoder,
+};
+use std::net::SocketAddr;
+use tracing::{error, info};
+
+use crate::{db, players};
+
+lazy_static! {
+    // dispatcher
+    pub static ref PACKETS_HANDLED: IntCounterVec = register_int_counter_vec!(
+        "rosu_packets_handled_total",
+        "Packets handled by the dispatcher",
+        &["packet"]
+    )
+    .unwrap();
+    pub static ref PACKET_LATENCY: HistogramVec = register_histogram_vec!(
+        "rosu_packet_handle_seconds",
+        "Time spent handling a packet",
+        &["packet"],
+        exponential_buckets(0.00001, 4.0, 10).unwrap() // 10us -> ~2.6s
+    )
+    .unwrap();
+
+    // logins
+    pub static ref LOGINS: IntCounterVec = register_int_counter_vec!(
+        "rosu_logins_total",
+        "Login attempts by outcome",
+        &["outcome"]
+    )
+    .unwrap();
+    pub static ref LOGIN_LATENCY: Histogram = register_histogram!(
+        "rosu_login_seconds",
+        "Time taken to handle a login",
+        exponential_buckets(0.001, 2.0, 12).unwrap() // 1ms -> ~2s
+    )
+    .unwrap();
+
+    // anticheat
+    pub static ref ANTICHEAT_FLAGS: IntCounterVec = register_int_counter_vec!(
+        "rosu_anticheat_flags_total",
+        "Users flagged by each anticheat detector",
+        &["detector"]
+    )
+    .unwrap();
+
+    // pubsub
+    pub static ref PUBSUB_MESSAGES: IntCounterVec = register_int_counter_vec!(
+        "rosu_pubsub_messages_total",
+        "Redis pubsub messages handled by channel",
+        &["channel"]
+    )
+    .unwrap();
+
+    // sampled on scrape
+    static ref ONLINE_BY_MODE: IntGaugeVec = register_int_gauge_vec!(
+        "rosu_online_users_by_mode",
+        "Online users by the mode they have selected",
+        &["mode"]
+    )
+    .unwrap();
+    static ref ONLINE_BY_ACTION: IntGaugeVec = register_int_gauge_vec!(
+        "rosu_online_users_by_action",
+        "Online users by their current action",
+        &["action"]
+    )
+    .unwrap();
+    static ref QUEUED_BYTES: IntGauge = register_int_gauge!(
+        "rosu_queued_bytes",
+        "Bytes waiting in packet queues for users to poll"
+    )
+    .unwrap();
+    static ref LARGEST_QUEUE_BYTES: IntGauge = register_int_gauge!(
+        "rosu_largest_queue_bytes",
+        "Bytes waiting in the largest single packet queue"
+    )
+    .unwrap();
+    static ref DB_CONNECTIONS: IntGauge = register_int_gauge!(
+        "rosu_mysql_connections",
+        "Open connections in the MySQL pool"
+    )
+    .unwrap();
+    static ref DB_IDLE_CONNECTIONS: IntGauge = register_int_gauge!(
+        "rosu_mysql_idle_connections",
+        "Idle connections in the MySQL pool"
+    )
+    .unwrap();
+}
+
+// updates the gauges that are cheaper to sample than to keep up to date
+async fn sample() {
+    ONLINE_BY_MODE.reset();
+    ONLINE_BY_ACTION.reset();
+
+    let mut queued = 0;
+    let mut largest = 0;
+
+    for u in players.all() {
+        let user = u.read().await;
+
+        ONLINE_BY_MODE
+            .with_label_values(&[user.current_mode.into()])
+            .inc();
+        ONLINE_BY_ACTION
+            .with_label_values(&[user.action.into()])
+            .inc();
+
+        let size = user.queue.size().await;
+        queued += size;
+        largest = largest.max(size);
+    }
+
+    QUEUED_BYTES.set(queued as i64);
+    LARGEST_QUEUE_BYTES.set(largest as i64);
+
+    if let Some(pool) = db.get() {
+        DB_CONNECTIONS.set(pool.size() as i64);
+        DB_IDLE_CONNECTIONS.set(pool.num_idle() as i64);
+    }
+}
+
+async fn metrics() -> HttpResponse {
+    sample().await;
+
+    let encoder = TextEncoder::new();
+    let mut buf = Vec::new();
+
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buf) {
+        error!(error = %e, "failed to encode metrics");
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    return HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(buf);
+}
+
+// Serves `/metrics` on its own address, so it can stay local while bancho is public.
+pub fn serve(addr: SocketAddr) -> std::io::Result<ntex::server::Server> {
+    let server = web::server(|| App::new().service(web::resource("/metrics").to(metrics)))
+        .workers(1)
+        .disable_signals()
+        .bind(addr)?
+        .run();
+
+    info!(%addr, "serving metrics");
+    return Ok(server);
+}
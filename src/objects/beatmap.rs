@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::constants::grade::{Grade, Hits};
+use crate::constants::mode::Mode;
+use crate::db;
+use crate::packets::structs::BeatmapInfo;
+
+// keeps each IN (...) list to a sane size, song select can ask about thousands of maps
+const LOOKUP_CHUNK: usize = 500;
+
+#[inline(always)]
+fn placeholders(count: usize) -> String {
+    return vec!["?"; count].join(", ");
+}
+
+#[derive(sqlx::FromRow)]
+pub struct Beatmap {
+    pub beatmap_id: i32,
+    pub beatmapset_id: i32,
+    pub beatmap_md5: String,
+    pub ranked: i8, // ripple's ranked statuses line up with what the client expects
+    pub file_name: Option<String>,
+}
+
+impl Beatmap {
+    pub async fn from_filenames(filenames: &[String]) -> sqlx::Result<Vec<Self>> {
+        let mut maps = Vec::new();
+
+        for chunk in filenames.chunks(LOOKUP_CHUNK) {
+            let query = format!(
+                "select beatmap_id, beatmapset_id, beatmap_md5, ranked, file_name 
+                from beatmaps where file_name in ({})",
+                placeholders(chunk.len())
+            );
+
+            let mut rows = sqlx::query_as::<_, Self>(&query);
+            for filename in chunk {
+                rows = rows.bind(filename);
+            }
+
+            maps.extend(rows.fetch_all(db.get().unwrap()).await?);
+        }
+
+        return Ok(maps);
+    }
+
+    pub async fn from_ids(ids: &[i32]) -> sqlx::Result<Vec<Self>> {
+        let mut maps = Vec::new();
+
+        for chunk in ids.chunks(LOOKUP_CHUNK) {
+            let query = format!(
+                "select beatmap_id, beatmapset_id, beatmap_md5, ranked, file_name 
+                from beatmaps where beatmap_id in ({})",
+                placeholders(chunk.len())
+            );
+
+            let mut rows = sqlx::query_as::<_, Self>(&query);
+            for id in chunk {
+                rows = rows.bind(id);
+            }
+
+            maps.extend(rows.fetch_all(db.get().unwrap()).await?);
+        }
+
+        return Ok(maps);
+    }
+
+    pub fn info(&self, index: i16, grades: [Grade; 4]) -> BeatmapInfo {
+        return BeatmapInfo {
+            index: index,
+            beatmap_id: self.beatmap_id,
+            beatmapset_id: self.beatmapset_id,
+            thread_id: 0,
+            ranked_status: self.ranked,
+            osu_grade: grades[0] as u8,
+            taiko_grade: grades[1] as u8,
+            catch_grade: grades[2] as u8,
+            mania_grade: grades[3] as u8,
+            map_md5: self.beatmap_md5.clone(),
+        };
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct BestScore {
+    beatmap_md5: String,
+    play_mode: i8,
+    mods: i32,
+    #[sqlx(rename = "300_count")]
+    n300: i32,
+    #[sqlx(rename = "100_count")]
+    n100: i32,
+    #[sqlx(rename = "50_count")]
+    n50: i32,
+    #[sqlx(rename = "gekis_count")]
+    geki: i32,
+    #[sqlx(rename = "katus_count")]
+    katu: i32,
+    #[sqlx(rename = "misses_count")]
+    miss: i32,
+}
+
+// The user's best grade on each map in each vanilla mode, from the scores
+// table matching their current mode (relax and autopilot have their own).
+pub async fn best_grades(
+    user_id: i32,
+    mode: Mode,
+    md5s: &[String],
+) -> sqlx::Result<HashMap<String, [Grade; 4]>> {
+    let mut grades: HashMap<String, [Grade; 4]> = HashMap::new();
+
+    for chunk in md5s.chunks(LOOKUP_CHUNK) {
+        // completed = 3 is the user's best score on the map
+        let query = format!(
+            "select beatmap_md5, play_mode, mods, 300_count, 100_count, 50_count, 
+            gekis_count, katus_count, misses_count 
+            from {} where userid = ? and completed = 3 and beatmap_md5 in ({})",
+            mode.scores_table(),
+            placeholders(chunk.len())
+        );
+
+        let mut rows = sqlx::query_as::<_, BestScore>(&query).bind(user_id);
+        for md5 in chunk {
+            rows = rows.bind(md5);
+        }
+
+        for score in rows.fetch_all(db.get().unwrap()).await? {
+            let play_mode = score.play_mode as i32;
+            if !(0..=3).contains(&play_mode) {
+                continue;
+            }
+
+            let hits = Hits {
+                n300: score.n300,
+                n100: score.n100,
+                n50: score.n50,
+                geki: score.geki,
+                katu: score.katu,
+                miss: score.miss,
+            };
+
+            let map_grades = grades.entry(score.beatmap_md5).or_insert([Grade::N; 4]);
+            map_grades[play_mode as usize] = Grade::from_hits(play_mode, score.mods, &hits);
+        }
+    }
+
+    return Ok(grades);
+}
@@ -0,0 +1,118 @@
+use dashmap::{DashMap, DashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::capture::{CaptureRecord, Direction};
+
+const CAPTURE_DIR: &str = "captures";
+
+// Tracks which sessions are being captured and their open capture files.
+// captures are opt-in, either for specific users or globally.
+pub struct CaptureList {
+    global: AtomicBool,
+    users: DashSet<i32>,
+    files: DashMap<i32, Arc<Mutex<File>>>,
+}
+
+impl CaptureList {
+    pub fn new() -> Self {
+        return Self {
+            global: AtomicBool::new(false),
+            users: DashSet::new(),
+            files: DashMap::new(),
+        };
+    }
+
+    pub fn set_global(&self, enabled: bool) {
+        self.global.store(enabled, Ordering::Relaxed);
+
+        if !enabled {
+            // close the files of anyone not captured individually
+            self.files.retain(|user_id, _| self.users.contains(user_id));
+        }
+    }
+
+    pub fn enable(&self, user_id: i32) {
+        self.users.insert(user_id);
+    }
+
+    pub fn disable(&self, user_id: i32) {
+        self.users.remove(&user_id);
+
+        if !self.global.load(Ordering::Relaxed) {
+            self.files.remove(&user_id);
+        }
+    }
+
+    // Closes the user's capture file when their session ends, the next one gets a new file.
+    pub fn close(&self, user_id: i32) {
+        self.files.remove(&user_id);
+    }
+
+    pub fn is_capturing(&self, user_id: i32) -> bool {
+        return self.global.load(Ordering::Relaxed) || self.users.contains(&user_id);
+    }
+
+    async fn file_for(&self, user_id: i32) -> std::io::Result<Arc<Mutex<File>>> {
+        if let Some(file) = self.files.get(&user_id) {
+            return Ok(file.clone());
+        }
+
+        fs::create_dir_all(CAPTURE_DIR).await?;
+
+        let path: PathBuf = [
+            CAPTURE_DIR,
+            &format!("{}-{}.rcap", user_id, unix_ms() / 1000),
+        ]
+        .iter()
+        .collect();
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+
+        info!(user_id, path = %path.display(), "capturing session");
+
+        let file = Arc::new(Mutex::new(file));
+        self.files.insert(user_id, file.clone());
+        return Ok(file);
+    }
+
+    // Appends a request or response body to the user's capture, if they're being captured.
+    pub async fn record(&self, user_id: i32, direction: Direction, data: &[u8]) {
+        if !self.is_capturing(user_id) {
+            return;
+        }
+
+        let record = CaptureRecord {
+            direction: direction,
+            timestamp_ms: unix_ms(),
+            user_id: user_id,
+            data: data.to_vec(),
+        };
+
+        let result = match self.file_for(user_id).await {
+            Ok(file) => file.lock().await.write_all(&record.serialise()).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            error!(user_id, error = %e, "failed to write capture");
+        }
+    }
+}
+
+fn unix_ms() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+}
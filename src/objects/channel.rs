@@ -3,6 +3,7 @@ use crate::objects::user::User;
 use crate::packets::handlers::channel_message;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tracing::warn;
 
 // Structure representing an in-game channel meant for chatting.
 pub struct Channel {
@@ -21,15 +22,15 @@ impl Channel {
 
     // Handles removing a user from the channel.
     pub async fn remove_user(&self, user_id: i32) {
-        if let Some(user_locked) = self.users.get_id(user_id.clone()).await {
+        if let Some(user_locked) = self.users.get_id(user_id) {
             let mut user = user_locked.write().await;
 
             // Remove channel arc.
             user.channels.remove(&self.name);
 
-            self.users.remove(user_id).await;
+            self.users.remove(user_id, &user.token);
         } else {
-            println!("Tried to remove a user from a channel they weren't a part of?");
+            warn!(user_id, channel = %self.name, "tried to remove a user from a channel they weren't a part of");
         }
     }
 
@@ -38,7 +39,7 @@ impl Channel {
         self.users
             .enqueue(channel_message(
                 player.username.clone(),
-                player.id.clone(),
+                player.id,
                 content,
                 self.name.clone(),
             ))
@@ -46,7 +47,7 @@ impl Channel {
     }
 
     pub async fn send_message_userid(&self, user_id: i32, content: String) {
-        if let Some(player_locked) = self.users.get_id(user_id).await {
+        if let Some(player_locked) = self.users.get_id(user_id) {
             self.send_message(player_locked, content).await;
         }
     }
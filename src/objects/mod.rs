@@ -1,6 +1,15 @@
+pub mod beatmap;
+pub mod captures;
 pub mod channel;
 pub mod mods;
 pub mod players;
+pub mod presence;
 pub mod queue;
+pub mod ratelimit;
+pub mod recordings;
+pub mod settings;
+pub mod snapshot;
+pub mod spectating;
 pub mod stats;
 pub mod user;
+pub mod writes;
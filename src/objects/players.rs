@@ -1,73 +1,219 @@
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{Mutex, RwLock};
+use dashmap::DashMap;
+use ntex::util::Bytes;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
+use crate::objects::presence::Presence;
+use crate::objects::queue::PacketQueue;
+use crate::objects::snapshot::Snapshot;
+use crate::objects::spectating::Spectating;
 use crate::objects::user::User;
 
+// we keep the lookup keys and shared state next to the user so removing a session,
+// broadcasting or spectating never needs to lock the user itself (handlers hold its write lock).
+struct PlayerEntry {
+    token: String,
+    username_safe: String,
+    queue: Arc<PacketQueue>,
+    presence: Arc<Presence>,
+    snapshot: Arc<Snapshot>,
+    spectating: Arc<Spectating>,
+    user: Arc<RwLock<User>>,
+}
+
+impl PlayerEntry {
+    fn new(user: &User, player: Arc<RwLock<User>>) -> Self {
+        // whatever was set up since the user was loaded (login, takeover) is in the packets from now on
+        user.snapshot.update(user);
+
+        return Self {
+            token: user.token.clone(),
+            username_safe: user.username_safe.clone(),
+            queue: user.queue.clone(),
+            presence: user.presence.clone(),
+            snapshot: user.snapshot.clone(),
+            spectating: user.spectating.clone(),
+            user: player,
+        };
+    }
+}
+
+// Sharded player list, with secondary indexes so lookups by token
+// or username are O(1) and never touch any user's lock.
 pub struct PlayerList {
-    pub players: Mutex<HashMap<i32, Arc<RwLock<User>>>>,
+    players: DashMap<i32, PlayerEntry>,
+    tokens: DashMap<String, Arc<RwLock<User>>>,
+    usernames: DashMap<String, Arc<RwLock<User>>>,
 }
 
 impl PlayerList {
     pub fn new() -> Self {
         return Self {
-            players: Mutex::new(HashMap::new()),
+            players: DashMap::new(),
+            tokens: DashMap::new(),
+            usernames: DashMap::new(),
         };
     }
 
-    pub async fn player_count(&self) -> usize {
-        return self.players.lock().await.len();
+    pub fn player_count(&self) -> usize {
+        return self.players.len();
     }
 
-    pub async fn add_player(&self, player: User) {
-        let user_id = player.id.clone();
+    pub fn add_player(&self, player: User) {
+        let user_id = player.id;
+        let player = Arc::from(RwLock::from(player));
 
-        let player_arc = Arc::from(RwLock::from(player));
-        self.players.lock().await.insert(user_id, player_arc);
+        // nobody else has the pointer yet, so this can't fail
+        let entry = PlayerEntry::new(&player.try_read().unwrap(), player.clone());
+        self.insert(user_id, entry);
     }
 
     // Adds an rwlocked player shared pointer to the player list.
     pub async fn add_player_ptr(&self, player: Arc<RwLock<User>>) {
-        let user_id = player.read().await.id.clone();
-        self.players.lock().await.insert(user_id, player);
+        let (user_id, entry) = {
+            let user = player.read().await;
+            (user.id, PlayerEntry::new(&user, player.clone()))
+        };
+
+        self.insert(user_id, entry);
     }
 
-    pub async fn enqueue(&self, bytes: Vec<u8>) {
-        for player in self.players.lock().await.values() {
-            let user = player.read().await;
-            user.enqueue(bytes.clone()).await;
+    fn insert(&self, user_id: i32, entry: PlayerEntry) {
+        let token = entry.token.clone();
+        let username_safe = entry.username_safe.clone();
+
+        self.tokens.insert(token.clone(), entry.user.clone());
+        self.usernames
+            .insert(username_safe.clone(), entry.user.clone());
+
+        // a relogging user replaces their old session, so drop its stale keys
+        if let Some(old) = self.players.insert(user_id, entry) {
+            if old.token != token {
+                self.tokens.remove(&old.token);
+            }
+
+            if old.username_safe != username_safe {
+                self.usernames.remove(&old.username_safe);
+            }
         }
     }
 
-    pub async fn get_id(&self, user_id: i32) -> Option<Arc<RwLock<User>>> {
-        match self.players.lock().await.get(&user_id) {
-            Some(u) => Some(u.clone()),
-            _ => None,
-        }
+    // Snapshot of every online user, so callers can await on them
+    // without holding any of the map's shard locks.
+    pub fn all(&self) -> Vec<Arc<RwLock<User>>> {
+        return self.players.iter().map(|e| e.user.clone()).collect();
     }
 
-    pub async fn get_username(&self, username: &str) -> Option<Arc<RwLock<User>>> {
-        for u in self.players.lock().await.values() {
-            let player = u.read().await;
-            if &player.username == username {
-                return Some(u.clone());
-            }
+    // Encodes once and hands every queue a reference to the same buffer.
+    pub async fn enqueue<B: Into<Bytes>>(&self, bytes: B) {
+        let bytes = bytes.into();
+        let queues = self
+            .players
+            .iter()
+            .map(|e| e.queue.clone())
+            .collect::<Vec<_>>();
+
+        for queue in queues {
+            queue.enqueue(bytes.clone()).await;
         }
+    }
 
-        return None;
+    // Like `enqueue`, but only to users whose update filter lets through
+    // updates about `user_id`. the user themselves always gets it.
+    pub async fn enqueue_presence<B: Into<Bytes>>(&self, user_id: i32, bytes: B) {
+        let bytes = bytes.into();
+        let queues = self
+            .players
+            .iter()
+            .filter(|e| *e.key() == user_id || e.presence.wants(user_id))
+            .map(|e| e.queue.clone())
+            .collect::<Vec<_>>();
+
+        for queue in queues {
+            queue.enqueue(bytes.clone()).await;
+        }
     }
 
-    pub async fn get_token(&self, token: &str) -> Option<Arc<RwLock<User>>> {
-        for u in self.players.lock().await.values() {
-            let player = u.read().await;
-            if &player.token == token {
-                return Some(u.clone());
-            }
+    // Sends to the given users through their queues, so none of them are locked.
+    pub async fn enqueue_to<B: Into<Bytes>>(&self, user_ids: &[i32], bytes: B) {
+        let bytes = bytes.into();
+        let queues = user_ids
+            .iter()
+            .filter_map(|id| self.players.get(id).map(|e| e.queue.clone()))
+            .collect::<Vec<_>>();
+
+        for queue in queues {
+            queue.enqueue(bytes.clone()).await;
         }
+    }
+
+    pub fn get_id(&self, user_id: i32) -> Option<Arc<RwLock<User>>> {
+        return self.players.get(&user_id).map(|e| e.user.clone());
+    }
+
+    pub fn spectating(&self, user_id: i32) -> Option<Arc<Spectating>> {
+        return self.players.get(&user_id).map(|e| e.spectating.clone());
+    }
+
+    pub fn snapshot(&self, user_id: i32) -> Option<Arc<Snapshot>> {
+        return self.players.get(&user_id).map(|e| e.snapshot.clone());
+    }
 
-        return None;
+    // Every online user's snapshot, for answering presence requests without locking anyone.
+    pub fn snapshots(&self) -> Vec<(i32, Arc<Snapshot>)> {
+        return self
+            .players
+            .iter()
+            .map(|e| (*e.key(), e.snapshot.clone()))
+            .collect();
     }
 
-    pub async fn remove(&self, user_id: i32) {
-        self.players.lock().await.remove(&user_id);
+    pub fn get_username(&self, username: &str) -> Option<Arc<RwLock<User>>> {
+        let username_safe = username.to_lowercase().replace(" ", "_");
+        return self.usernames.get(&username_safe).map(|u| u.clone());
+    }
+
+    pub fn get_token(&self, token: &str) -> Option<Arc<RwLock<User>>> {
+        return self.tokens.get(token).map(|u| u.clone());
+    }
+
+    pub fn ids(&self) -> Vec<i32> {
+        return self.players.iter().map(|e| *e.key()).collect();
+    }
+
+    // Removes the user's session, unless it's already been replaced by a newer one.
+    pub fn remove(&self, user_id: i32, token: &str) {
+        if let Some((_, entry)) = self.players.remove_if(&user_id, |_, e| e.token == token) {
+            self.tokens.remove(&entry.token);
+            self.usernames.remove(&entry.username_safe);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_sessions_dont_remove_their_replacement() {
+        let list = PlayerList::new();
+        let old = User::stub(9201, "relogger");
+        let old_token = old.token.clone();
+        list.add_player(old);
+
+        let new = User::stub(9201, "relogger");
+        let new_token = new.token.clone();
+        list.add_player(new);
+
+        // the old session logging out or being reaped leaves the new one alone
+        list.remove(9201, &old_token);
+        assert!(list.get_id(9201).is_some());
+        assert!(list.get_token(&new_token).is_some());
+        assert!(list.get_username("relogger").is_some());
+        assert!(list.get_token(&old_token).is_none());
+
+        list.remove(9201, &new_token);
+        assert!(list.get_id(9201).is_none());
+        assert!(list.get_username("relogger").is_none());
     }
 }
@@ -0,0 +1,88 @@
+use dashmap::DashSet;
+use num_traits::FromPrimitive;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::constants::presence::PresenceFilter;
+
+// A user's update filter and friends, shared with the player list so broadcasts
+// can tell who wants an update without locking every user.
+pub struct Presence {
+    filter: AtomicU8,
+    friends: DashSet<i32>,
+}
+
+impl Presence {
+    pub fn new(friends: Vec<i32>) -> Self {
+        return Self {
+            filter: AtomicU8::new(PresenceFilter::All as u8),
+            friends: friends.into_iter().collect(),
+        };
+    }
+
+    pub fn filter(&self) -> PresenceFilter {
+        return PresenceFilter::from_u8(self.filter.load(Ordering::Relaxed))
+            .unwrap_or(PresenceFilter::All);
+    }
+
+    pub fn set_filter(&self, filter: PresenceFilter) {
+        self.filter.store(filter as u8, Ordering::Relaxed);
+    }
+
+    pub fn friends(&self) -> Vec<i32> {
+        return self.friends.iter().map(|id| *id).collect();
+    }
+
+    pub fn is_friend(&self, user_id: i32) -> bool {
+        return self.friends.contains(&user_id);
+    }
+
+    // returns false if they were already a friend
+    pub fn add_friend(&self, user_id: i32) -> bool {
+        return self.friends.insert(user_id);
+    }
+
+    // returns false if they weren't a friend
+    pub fn remove_friend(&self, user_id: i32) -> bool {
+        return self.friends.remove(&user_id).is_some();
+    }
+
+    // Whether updates about `user_id` should be sent, not counting the user's own updates.
+    pub fn wants(&self, user_id: i32) -> bool {
+        return match self.filter() {
+            PresenceFilter::Nobody => false,
+            PresenceFilter::All => true,
+            PresenceFilter::Friends => self.is_friend(user_id),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let presence = Presence::new(vec![2]);
+        assert!(presence.wants(2) && presence.wants(3));
+
+        presence.set_filter(PresenceFilter::Friends);
+        assert!(presence.wants(2) && !presence.wants(3));
+
+        presence.set_filter(PresenceFilter::Nobody);
+        assert!(!presence.wants(2) && !presence.wants(3));
+    }
+
+    #[test]
+    fn friends() {
+        let presence = Presence::new(vec![2]);
+        presence.set_filter(PresenceFilter::Friends);
+
+        assert!(!presence.add_friend(2));
+        assert!(presence.add_friend(3));
+        assert!(presence.wants(3));
+
+        assert!(presence.remove_friend(2));
+        assert!(!presence.remove_friend(2));
+        assert!(!presence.wants(2));
+    }
+}
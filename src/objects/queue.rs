@@ -1,26 +1,42 @@
+use ntex::util::{Bytes, BytesMut};
 use tokio::sync::Mutex;
 
+// Queue of encoded packets waiting for the user's next poll.
+// segments are reference counted, so a broadcast is encoded once and
+// every user's queue just holds another handle to the same buffer.
 pub struct PacketQueue {
-    queue: Mutex<Vec<u8>>,
+    queue: Mutex<Vec<Bytes>>,
 }
 
 impl PacketQueue {
     pub fn new() -> Self {
         return Self {
-            queue: Mutex::new(Vec::with_capacity(512)),
+            queue: Mutex::new(Vec::with_capacity(16)),
         };
     }
 
     #[inline(always)]
-    pub async fn dequeue(&self) -> Vec<u8> {
-        let mut queue = self.queue.lock().await;
-        let queue_vec = queue.clone();
+    pub async fn dequeue(&self) -> Bytes {
+        let segments = std::mem::take(&mut *self.queue.lock().await);
 
-        queue.clear();
-        return queue_vec;
+        if segments.len() == 1 {
+            return segments.into_iter().next().unwrap();
+        }
+
+        let mut buf = BytesMut::with_capacity(segments.iter().map(|s| s.len()).sum());
+        for segment in segments {
+            buf.extend_from_slice(&segment);
+        }
+
+        return buf.freeze();
+    }
+
+    // bytes waiting to be dequeued
+    pub async fn size(&self) -> usize {
+        return self.queue.lock().await.iter().map(|s| s.len()).sum();
     }
 
-    pub async fn enqueue(&self, bytes: Vec<u8>) {
-        self.queue.lock().await.extend(bytes);
+    pub async fn enqueue<B: Into<Bytes>>(&self, bytes: B) {
+        self.queue.lock().await.push(bytes.into());
     }
 }
@@ -0,0 +1,38 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+// Lets each user do something at most once per interval, across relogs.
+pub struct RateLimiter {
+    interval: Duration,
+    last: DashMap<i32, Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        return Self {
+            interval: interval,
+            last: DashMap::new(),
+        };
+    }
+
+    // Whether the user is allowed to go ahead now, counting it if they are.
+    pub fn check(&self, user_id: i32) -> bool {
+        let now = Instant::now();
+
+        match self.last.entry(user_id) {
+            Entry::Occupied(mut entry) => {
+                if now.duration_since(*entry.get()) < self.interval {
+                    return false;
+                }
+
+                entry.insert(now);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+            }
+        }
+
+        return true;
+    }
+}
@@ -0,0 +1,207 @@
+use dashmap::{DashMap, DashSet};
+use num_traits::FromPrimitive;
+use std::io;
+use std::path::PathBuf;
+use tokio::fs;
+use tracing::{error, info, warn};
+
+use crate::constants::mode::Mode;
+use crate::constants::replay::ReplayAction;
+use crate::objects::mods::Mods;
+use crate::objects::user::User;
+use crate::objects::writes::unix_time;
+use crate::packets::structs::{ReplayFrame, ReplayFrameBundle, ScoreFrame};
+use crate::replay::Replay;
+
+const REPLAY_DIR: &str = "replays";
+const MAX_FRAMES: usize = 100_000; // about half an hour of play, anything past it is dropped
+
+// One play being recorded, for the map, mods and mode the host had when it started.
+struct Recording {
+    username: String,
+    version: i32,
+    map_md5: String,
+    mods: Mods,
+    mode: Mode,
+    started: i32,
+    frames: Vec<ReplayFrame>,
+    score: ScoreFrame, // the latest one, so the final score once the play ends
+}
+
+impl Recording {
+    fn new(user: &User) -> Self {
+        return Self {
+            username: user.username.clone(),
+            version: client_version(&user.osuver),
+            map_md5: user.map_md5.clone(),
+            mods: user.mods,
+            mode: user.current_mode,
+            started: unix_time(),
+            frames: Vec::new(),
+            score: ScoreFrame::default(),
+        };
+    }
+
+    fn matches(&self, user: &User) -> bool {
+        return self.map_md5 == user.map_md5
+            && self.mods == user.mods
+            && self.mode == user.current_mode;
+    }
+
+    fn into_replay(self) -> Replay {
+        return Replay {
+            mode: self.mode.as_vn() as u8,
+            version: self.version,
+            map_md5: self.map_md5,
+            username: self.username,
+            score: self.score,
+            mods: self.mods.bits(),
+            timestamp: self.started as i64,
+            frames: self.frames,
+        };
+    }
+}
+
+// "b20220101.2" -> 20220101
+fn client_version(osu_ver: &str) -> i32 {
+    return osu_ver
+        .trim_start_matches('b')
+        .split('.')
+        .next()
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(0);
+}
+
+// the md5 comes straight from the client, so anything that isn't one gets hashed
+// rather than put in a path as is
+fn file_safe_md5(map_md5: &str) -> String {
+    if map_md5.len() == 32 && map_md5.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return map_md5.to_lowercase();
+    }
+
+    return format!("{:x}", md5::compute(map_md5));
+}
+
+async fn write_replay(path: &PathBuf, replay: Replay) -> io::Result<()> {
+    fs::create_dir_all(REPLAY_DIR).await?;
+    return fs::write(path, replay.serialise()?).await;
+}
+
+// Records the frames of flagged users' plays to .osr files in `replays/`, so staff
+// can review them later. the client only sends frames while someone is spectating it.
+pub struct RecordingList {
+    users: DashSet<i32>,
+    recordings: DashMap<i32, Recording>,
+}
+
+impl RecordingList {
+    pub fn new() -> Self {
+        return Self {
+            users: DashSet::new(),
+            recordings: DashMap::new(),
+        };
+    }
+
+    pub fn enable(&self, user_id: i32) {
+        self.users.insert(user_id);
+    }
+
+    // Stops recording the user, dropping the play in progress.
+    pub fn disable(&self, user_id: i32) {
+        self.users.remove(&user_id);
+        self.recordings.remove(&user_id);
+    }
+
+    pub fn is_recording(&self, user_id: i32) -> bool {
+        return self.users.contains(&user_id);
+    }
+
+    // Adds a frame bundle from the user to their current play, saving it once it ends.
+    pub async fn record(&self, user: &User, bundle: &ReplayFrameBundle) {
+        if !self.is_recording(user.id) {
+            return;
+        }
+
+        let action = ReplayAction::from_u8(bundle.action).unwrap_or(ReplayAction::Standard);
+
+        // a new song, or their status changing under us, ends whatever was being recorded
+        let stale = match self.recordings.get(&user.id) {
+            Some(recording) => action == ReplayAction::NewSong || !recording.matches(user),
+            _ => false,
+        };
+
+        if stale {
+            self.finish(user.id).await;
+        }
+
+        if !bundle.frames.is_empty() {
+            let mut recording = self
+                .recordings
+                .entry(user.id)
+                .or_insert_with(|| Recording::new(user));
+
+            let room = MAX_FRAMES.saturating_sub(recording.frames.len());
+            if room < bundle.frames.len() && room > 0 {
+                warn!(
+                    user_id = user.id,
+                    "recording is too long, dropping the rest of its frames"
+                );
+            }
+
+            recording
+                .frames
+                .extend(bundle.frames.iter().take(room).cloned());
+            recording.score = bundle.score_frame.clone();
+        }
+
+        match action {
+            ReplayAction::Completion | ReplayAction::Fail | ReplayAction::SongSelect => {
+                self.finish(user.id).await
+            }
+            _ => (),
+        }
+    }
+
+    // Saves the user's play in progress, if they have one.
+    pub async fn finish(&self, user_id: i32) {
+        let recording = match self.recordings.remove(&user_id) {
+            Some((_, recording)) if !recording.frames.is_empty() => recording,
+            _ => return,
+        };
+
+        let path: PathBuf = [
+            REPLAY_DIR,
+            &format!(
+                "{}-{}-{}.osr",
+                user_id,
+                recording.started,
+                file_safe_md5(&recording.map_md5)
+            ),
+        ]
+        .iter()
+        .collect();
+
+        match write_replay(&path, recording.into_replay()).await {
+            Ok(()) => info!(user_id, path = %path.display(), "saved replay"),
+            Err(e) => error!(user_id, error = %e, "failed to save replay"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5s_are_file_safe() {
+        let md5 = "0123456789abcdef0123456789ABCDEF";
+        assert_eq!(file_safe_md5(md5), md5.to_lowercase());
+
+        for map_md5 in &["", "../../etc/passwd", "0123456789abcdef0123456789abcdeg"] {
+            let safe = file_safe_md5(map_md5);
+
+            assert_eq!(safe.len(), 32);
+            assert!(safe.bytes().all(|b| b.is_ascii_hexdigit()));
+        }
+    }
+}
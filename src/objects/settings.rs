@@ -0,0 +1,172 @@
+use crate::packets::handlers;
+use crate::{db, players, settings};
+
+use ntex::util::Bytes;
+use std::time::Duration;
+use tracing::{error, info};
+
+// how long non-staff get before they're disconnected for maintenance
+const MAINTENANCE_COUNTDOWN_SECS: u64 = 30;
+
+#[derive(sqlx::FromRow)]
+struct SettingRow {
+    name: String,
+    value_int: i32,
+    value_string: String,
+}
+
+// Runtime settings loaded from the bancho_settings table.
+// held in memory so handlers don't have to hit mysql on every request.
+pub struct BanchoSettings {
+    pub maintenance: bool,
+    pub free_direct: bool,
+    pub menu_icon: Option<(String, String)>, // (image url, click url)
+    pub login_notification: Option<String>,
+    pub restricted_notification: Option<String>,
+}
+
+impl BanchoSettings {
+    pub fn new() -> Self {
+        return Self {
+            maintenance: false,
+            free_direct: false,
+            menu_icon: None,
+            login_notification: None,
+            restricted_notification: None,
+        };
+    }
+
+    pub async fn from_sql() -> sqlx::Result<Self> {
+        let rows = sqlx::query_as::<_, SettingRow>(
+            "select name, value_int, value_string from bancho_settings",
+        )
+        .fetch_all(db.get().unwrap())
+        .await?;
+
+        let mut bancho_settings = Self::new();
+        for row in rows {
+            // for string settings, value_int is used as an "enabled" toggle
+            let enabled = row.value_int != 0;
+            let value_string = row.value_string;
+
+            match row.name.as_str() {
+                "bancho_maintenance" => bancho_settings.maintenance = enabled,
+                "free_direct" => bancho_settings.free_direct = enabled,
+                "menu_icon" if enabled => {
+                    let icon = value_string
+                        .split("|")
+                        .map(|s| s.trim().to_string())
+                        .collect::<Vec<String>>();
+
+                    if icon.len() == 2 {
+                        bancho_settings.menu_icon = Some((icon[0].clone(), icon[1].clone()));
+                    }
+                }
+                "login_notification" if enabled && !value_string.is_empty() => {
+                    bancho_settings.login_notification = Some(value_string);
+                }
+                "restricted_joke" if enabled && !value_string.is_empty() => {
+                    bancho_settings.restricted_notification = Some(value_string);
+                }
+                _ => (),
+            }
+        }
+
+        return Ok(bancho_settings);
+    }
+}
+
+// Reloads the global settings from mysql, swapping them in one go.
+// if mysql can't be reached the settings we already have are kept.
+pub async fn reload_settings() {
+    let new_settings = match BanchoSettings::from_sql().await {
+        Ok(new_settings) => new_settings,
+        Err(e) => {
+            error!(error = %e, "failed to reload bancho settings, keeping the current ones");
+            return;
+        }
+    };
+    let maintenance = new_settings.maintenance;
+
+    let was_maintenance = std::mem::replace(&mut *settings.write().await, new_settings).maintenance;
+    info!("reloaded bancho settings");
+
+    if maintenance && !was_maintenance {
+        disconnect_non_staff().await;
+    }
+}
+
+// Writes the maintenance setting, adding its row if the table doesn't have one yet.
+async fn store_maintenance(enabled: bool) -> sqlx::Result<()> {
+    let pool = db.get().unwrap();
+
+    // rows_affected can't tell us, an update that doesn't change the value affects no rows
+    let exists = sqlx::query("select 1 from bancho_settings where name = 'bancho_maintenance'")
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+
+    let query = match exists {
+        true => "UPDATE bancho_settings SET value_int = ? WHERE name = 'bancho_maintenance'",
+        false => "INSERT INTO bancho_settings (name, value_int, value_string) VALUES ('bancho_maintenance', ?, '')",
+    };
+
+    sqlx::query(query)
+        .bind(enabled as i32)
+        .execute(pool)
+        .await?;
+
+    return Ok(());
+}
+
+// Toggles maintenance mode, persisting it so it survives a restart. it's still
+// toggled if mysql can't be reached, it just won't last past a restart or reload.
+pub async fn set_maintenance(enabled: bool) {
+    if let Err(e) = store_maintenance(enabled).await {
+        error!(enabled, error = %e, "failed to store maintenance mode");
+    }
+
+    let was_maintenance = std::mem::replace(&mut settings.write().await.maintenance, enabled);
+    info!(enabled, "maintenance mode set");
+
+    if enabled && !was_maintenance {
+        disconnect_non_staff().await;
+    }
+}
+
+// Warns every online non-staff user, then relogs them once the countdown is up.
+// login rejects them while maintenance is on, so they stay out until it's turned off.
+async fn disconnect_non_staff() {
+    let mut packet_bytes = handlers::notification(&format!(
+        "rosu is entering maintenance mode, you will be disconnected in {} seconds.",
+        MAINTENANCE_COUNTDOWN_SECS
+    ));
+    packet_bytes.extend(handlers::server_restart(
+        (MAINTENANCE_COUNTDOWN_SECS * 1000) as i32,
+    ));
+    let packet_bytes = Bytes::from(packet_bytes);
+
+    for u in players.all() {
+        let user = u.read().await;
+
+        if !user.staff() {
+            user.enqueue(packet_bytes.clone()).await;
+        }
+    }
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(MAINTENANCE_COUNTDOWN_SECS)).await;
+
+        if !settings.read().await.maintenance {
+            return; // turned back off during the countdown
+        }
+
+        for u in players.all() {
+            let mut user = u.write().await;
+
+            if !user.staff() {
+                user.logout().await;
+            }
+        }
+    });
+}
@@ -0,0 +1,169 @@
+use ntex::util::Bytes;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::objects::user::User;
+use crate::packets::handlers;
+
+// A user's presence and stats packets as of their last change, shared with the player list
+// so other users' presence and stats requests never need this user's lock. the mutexes are
+// only held to swap the buffers, never across an await.
+pub struct Snapshot {
+    presence: Mutex<Bytes>,
+    stats: Mutex<Bytes>,
+    restricted: AtomicBool,
+}
+
+impl Snapshot {
+    pub fn new() -> Self {
+        return Self {
+            presence: Mutex::new(Bytes::new()),
+            stats: Mutex::new(Bytes::new()),
+            restricted: AtomicBool::new(false),
+        };
+    }
+
+    // Re-encodes the packets, call this whenever something they're made from changes.
+    pub fn update(&self, user: &User) {
+        *self.presence.lock().unwrap() = handlers::user_presence(user).into();
+        *self.stats.lock().unwrap() = handlers::user_stats(user).into();
+        self.restricted.store(user.restricted(), Ordering::Relaxed);
+    }
+
+    pub fn presence(&self) -> Bytes {
+        return self.presence.lock().unwrap().clone();
+    }
+
+    pub fn stats(&self) -> Bytes {
+        return self.stats.lock().unwrap().clone();
+    }
+
+    pub fn restricted(&self) -> bool {
+        return self.restricted.load(Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bancho;
+    use crate::constants::presence::PresenceFilter;
+    use crate::constants::privileges::Privileges;
+    use crate::objects::user::User;
+    use crate::packets::codec::BanchoPacket;
+    use crate::packets::structs::osu;
+    use crate::players;
+
+    // both users are mid-request, so their write locks are held the whole time
+    #[tokio::test]
+    async fn requests_never_lock_other_users() {
+        players.add_player(User::stub(9001, "requester"));
+        players.add_player(User::stub(9002, "busy"));
+
+        let requester = players.get_id(9001).unwrap();
+        let busy = players.get_id(9002).unwrap();
+        let _busy = busy.write().await;
+
+        let mut body = osu::UserStatsRequest {
+            user_ids: vec![9001, 9002],
+        }
+        .serialise();
+        body.extend(
+            osu::UserPresenceRequest {
+                user_ids: vec![9001, 9002],
+            }
+            .serialise(),
+        );
+        body.extend(osu::UserPresenceRequestAll { ingame_time: 0 }.serialise());
+
+        let mut player = requester.write().await;
+        let dispatched = bancho::dispatch(&mut player, body);
+        assert!(tokio::time::timeout(Duration::from_secs(5), dispatched)
+            .await
+            .is_ok());
+
+        let expected = [
+            _busy.snapshot.stats(),
+            _busy.snapshot.presence(),
+            player.snapshot.stats(),
+        ];
+        let response = player.dequeue().await;
+        for packet in &expected {
+            assert!(response
+                .windows(packet.len())
+                .any(|window| window == &packet[..]));
+        }
+
+        players.remove(9001, &player.token);
+        players.remove(9002, &_busy.token);
+    }
+
+    #[tokio::test]
+    async fn presence_requests_follow_the_filter() {
+        players.add_player(User::stub(9011, "picky"));
+        players.add_player(User::stub(9012, "friend"));
+        players.add_player(User::stub(9013, "stranger"));
+
+        let picky = players.get_id(9011).unwrap();
+        let mut player = picky.write().await;
+        player.presence.add_friend(9012);
+        player.presence.set_filter(PresenceFilter::Friends);
+
+        let mut body = osu::UserPresenceRequestAll { ingame_time: 0 }.serialise();
+        body.extend(
+            osu::UserPresenceRequest {
+                user_ids: vec![9012, 9013],
+            }
+            .serialise(),
+        );
+        bancho::dispatch(&mut player, body).await;
+
+        let response = player.dequeue().await;
+        let contains = |user_id: i32| {
+            let packet = players.snapshot(user_id).unwrap().presence();
+            return response
+                .windows(packet.len())
+                .any(|window| window == &packet[..]);
+        };
+
+        assert!(contains(9011));
+        assert!(contains(9012));
+        assert!(!contains(9013));
+
+        players.remove(9011, &player.token);
+        for user_id in 9012..=9013 {
+            let token = players.get_id(user_id).unwrap().read().await.token.clone();
+            players.remove(user_id, &token);
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_leave_out_restricted_users() {
+        let mut restricted = User::stub(9022, "restricted");
+        restricted.privileges = Privileges::USER_NORMAL;
+        let restricted_token = restricted.token.clone();
+        players.add_player(User::stub(9021, "requester"));
+        players.add_player(restricted);
+
+        let requester = players.get_id(9021).unwrap();
+        let mut player = requester.write().await;
+
+        let mut body = osu::UserStatsRequest {
+            user_ids: vec![9022],
+        }
+        .serialise();
+        body.extend(
+            osu::UserPresenceRequest {
+                user_ids: vec![9022],
+            }
+            .serialise(),
+        );
+        bancho::dispatch(&mut player, body).await;
+
+        assert!(player.dequeue().await.is_empty());
+
+        players.remove(9021, &player.token);
+        players.remove(9022, &restricted_token);
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+use tracing::info;
+
+use crate::cluster::{self, Event};
+use crate::packets::handlers;
+use crate::players;
+
+// Who a user is spectating and who is spectating them, shared with the player list
+// so spectating never needs another user's lock. only one of these mutexes is ever
+// held at a time and never across an await, so two users spectating each other can't deadlock.
+pub struct Spectating {
+    host: Mutex<Option<i32>>,
+    spectators: Mutex<Vec<i32>>,
+}
+
+impl Spectating {
+    pub fn new() -> Self {
+        return Self {
+            host: Mutex::new(None),
+            spectators: Mutex::new(Vec::new()),
+        };
+    }
+
+    pub fn host(&self) -> Option<i32> {
+        return *self.host.lock().unwrap();
+    }
+
+    // returns the host they were spectating before
+    pub fn set_host(&self, host_id: Option<i32>) -> Option<i32> {
+        return std::mem::replace(&mut *self.host.lock().unwrap(), host_id);
+    }
+
+    // Clears the host, unless they've already moved on to someone else.
+    pub fn clear_host(&self, host_id: i32) -> bool {
+        let mut host = self.host.lock().unwrap();
+        if *host != Some(host_id) {
+            return false;
+        }
+
+        *host = None;
+        return true;
+    }
+
+    pub fn spectators(&self) -> Vec<i32> {
+        return self.spectators.lock().unwrap().clone();
+    }
+
+    // returns false if they were already spectating
+    pub fn add_spectator(&self, user_id: i32) -> bool {
+        let mut spectators = self.spectators.lock().unwrap();
+        if spectators.contains(&user_id) {
+            return false;
+        }
+
+        spectators.push(user_id);
+        return true;
+    }
+
+    // returns false if they weren't spectating
+    pub fn remove_spectator(&self, user_id: i32) -> bool {
+        let mut spectators = self.spectators.lock().unwrap();
+        return match spectators.iter().position(|id| *id == user_id) {
+            Some(index) => {
+                spectators.remove(index);
+                true
+            }
+            _ => false,
+        };
+    }
+
+    pub fn take_spectators(&self) -> Vec<i32> {
+        return std::mem::take(&mut *self.spectators.lock().unwrap());
+    }
+}
+
+// The host's side of spectating, which has to happen wherever the host is being served.
+// these take ids so another instance can ask for them through the cluster.
+
+// Adds the spectator to the host's spectators, returns false if the host isn't here.
+pub async fn join(host_id: i32, spectator_id: i32) -> bool {
+    let host = match players.spectating(host_id) {
+        Some(host) => host,
+        _ => return false,
+    };
+
+    let fellows = host.spectators();
+    if !host.add_spectator(spectator_id) {
+        return true;
+    }
+
+    let mut packet_bytes = Vec::new();
+    for fellow_id in &fellows {
+        packet_bytes.extend(handlers::spectator_joined(*fellow_id));
+    }
+
+    if !packet_bytes.is_empty() {
+        cluster::enqueue_to(&[spectator_id], packet_bytes).await;
+    }
+
+    cluster::enqueue_to(&fellows, handlers::spectator_joined(spectator_id)).await;
+    cluster::enqueue_to(&[host_id], handlers::host_spectator_joined(spectator_id)).await;
+
+    info!(host_id, spectator_id, "started spectating");
+    return true;
+}
+
+pub async fn leave(host_id: i32, spectator_id: i32) {
+    let host = match players.spectating(host_id) {
+        Some(host) => host,
+        _ => return,
+    };
+
+    if !host.remove_spectator(spectator_id) {
+        return;
+    }
+
+    cluster::enqueue_to(&host.spectators(), handlers::spectator_left(spectator_id)).await;
+    cluster::enqueue_to(&[host_id], handlers::host_spectator_left(spectator_id)).await;
+
+    info!(host_id, spectator_id, "stopped spectating");
+}
+
+// Tells the host and everyone else watching that the spectator doesn't have the map.
+pub async fn cant_spectate(host_id: i32, spectator_id: i32) {
+    let mut user_ids = match players.spectating(host_id) {
+        Some(host) => host.spectators(),
+        _ => return,
+    };
+
+    user_ids.retain(|id| *id != spectator_id);
+    user_ids.push(host_id);
+
+    cluster::enqueue_to(&user_ids, handlers::spectator_cant_spectate(spectator_id)).await;
+}
+
+// The host left, so their spectators are left without one.
+pub async fn host_left(host_id: i32, spectators: Vec<i32>) {
+    let mut remote = Vec::new();
+
+    for spectator_id in spectators {
+        match players.spectating(spectator_id) {
+            Some(spectator) => {
+                spectator.clear_host(host_id);
+            }
+            _ => remote.push(spectator_id),
+        }
+    }
+
+    if !remote.is_empty() {
+        cluster::relay(Event::HostLeft {
+            host_id: host_id,
+            spectators: remote,
+        })
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spectators() {
+        let host = Spectating::new();
+
+        assert!(host.add_spectator(2));
+        assert!(!host.add_spectator(2));
+        assert!(host.add_spectator(3));
+        assert_eq!(host.spectators(), vec![2, 3]);
+
+        assert!(host.remove_spectator(2));
+        assert!(!host.remove_spectator(2));
+        assert_eq!(host.take_spectators(), vec![3]);
+        assert!(host.spectators().is_empty());
+    }
+
+    #[test]
+    fn hosts() {
+        let spectator = Spectating::new();
+
+        assert_eq!(spectator.set_host(Some(1)), None);
+        assert_eq!(spectator.set_host(Some(2)), Some(1));
+
+        // a stale cleanup from their old host leaves the new one alone
+        assert!(!spectator.clear_host(1));
+        assert_eq!(spectator.host(), Some(2));
+
+        assert!(spectator.clear_host(2));
+        assert_eq!(spectator.host(), None);
+    }
+}
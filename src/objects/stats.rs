@@ -2,7 +2,7 @@ use crate::db;
 
 use crate::constants::mode::Mode;
 
-#[derive(sqlx::FromRow)]
+#[derive(Default, sqlx::FromRow)]
 pub struct Stats {
     pub total_score: i32,
     pub ranked_score: i32,
@@ -13,7 +13,7 @@ pub struct Stats {
 }
 
 impl Stats {
-    pub async fn for_mode(mode: Mode, user_id: i32) -> Self {
+    pub async fn for_mode(mode: Mode, user_id: i32) -> sqlx::Result<Self> {
         let query: String = format!(
             "select total_score_{suffix} as total_score, ranked_score_{suffix} as ranked_score, 
             avg_accuracy_{suffix} as accuracy, playcount_{suffix} as playcount, pp_{suffix} as pp 
@@ -22,12 +22,9 @@ impl Stats {
             table = mode.stats_table(),
         );
 
-        let mode = sqlx::query_as::<_, Self>(&query)
+        return sqlx::query_as::<_, Self>(&query)
             .bind(user_id)
             .fetch_one(db.get().unwrap())
-            .await
-            .unwrap();
-
-        return mode;
+            .await;
     }
 }
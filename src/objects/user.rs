@@ -1,18 +1,25 @@
+use crate::cluster::{self, Event};
 use crate::constants::action::Action;
 use crate::constants::country::CountryCodes;
 use crate::constants::mode::Mode;
 use crate::constants::privileges::{BanchoPrivileges, Privileges};
 use crate::objects::channel::Channel;
 use crate::objects::mods::Mods;
+use crate::objects::presence::Presence;
 use crate::objects::queue::PacketQueue;
+use crate::objects::snapshot::Snapshot;
+use crate::objects::spectating::{self, Spectating};
 use crate::objects::stats::Stats;
 use crate::packets::handlers;
-use crate::{db, players};
+use crate::{captures, db, players, recordings};
 
+use ntex::util::Bytes;
 use uuid::Uuid;
 
 use std::str::FromStr;
+use std::time::Instant;
 use strum::IntoEnumIterator;
+use tracing::{error, info};
 
 use std::{collections::HashMap, sync::Arc};
 
@@ -58,6 +65,7 @@ pub_struct!(User {
 
     // set upon login, not from db.
     utc_offset: i32,
+    osu_md5: String, // client executable hash
     country: String,
     geoloc: u8,
     bancho_priv: BanchoPrivileges,
@@ -72,112 +80,189 @@ pub_struct!(User {
     current_mode: Mode,
     map_id: i32,
 
-    token: String,      // rando token
-    queue: PacketQueue, // for sending packets to the user
+    token: String,           // rando token
+    queue: Arc<PacketQueue>, // for sending packets to the user
+    last_request: Instant,   // used to time out dead sessions
+    kicked: bool,            // logged out once their queue has been flushed
 
     stats: Vec<Stats>,
-    friends: Vec<i32>,
+    presence: Arc<Presence>, // update filter & friends, shared with the player list
+    snapshot: Arc<Snapshot>, // presence & stats packets, shared with the player list
 
-    spectating: Option<i32>,
-    spectators: Vec<i32>,
+    spectating: Arc<Spectating>, // host & spectators, shared with the player list
     channels: HashMap<String, Arc<Channel>>,
 });
 
 impl User {
-    // perhaps the worst part of this entire code rn
+    // Loads the user for a login or takeover. a user that doesn't exist and a database
+    // error both come back as None, the error is logged.
     pub async fn from_sql(username: &str, token: Uuid, osu_ver: &str, offset: i32) -> Option<Self> {
+        return match Self::load(username, token, osu_ver, offset).await {
+            Ok(user) => user,
+            Err(e) => {
+                error!(error = %e, username, "failed to load user");
+                None
+            }
+        };
+    }
+
+    // perhaps the worst part of this entire code rn
+    async fn load(
+        username: &str,
+        token: Uuid,
+        osu_ver: &str,
+        offset: i32,
+    ) -> sqlx::Result<Option<Self>> {
         let user_row = sqlx::query!(
             "select * from users where username_safe = ?",
             username.to_lowercase().replace(" ", "_")
         )
-        .fetch_one(db.get().unwrap())
-        .await;
-
-        match user_row {
-            Ok(user_row) => {
-                let country =
-                    sqlx::query!("select country from users_stats where id = ?", user_row.id)
-                        .fetch_one(db.get().unwrap())
-                        .await
-                        .unwrap()
-                        .country;
-
-                let friend_rows = sqlx::query!(
-                    "select user2 from users_relationships where user1 = ?",
-                    user_row.id
-                )
-                .fetch_all(db.get().unwrap())
-                .await
-                .unwrap();
+        .fetch_optional(db.get().unwrap())
+        .await?;
 
-                let friends_vec = friend_rows.iter().map(|v| v.user2).collect::<Vec<i32>>();
-
-                let geoloc = CountryCodes::from_str(&country.to_uppercase())
-                    .unwrap_or(CountryCodes::XX) as u8;
-
-                let mut stats_vec: Vec<Stats> = Vec::new();
-                for mode in Mode::iter() {
-                    stats_vec.push(Stats::for_mode(mode, user_row.id).await);
-                }
-
-                return Some(Self {
-                    id: user_row.id,
-                    osuver: osu_ver.to_string(),
-                    username: user_row.username,
-                    username_safe: user_row.username_safe,
-                    ban_datetime: user_row.ban_datetime.parse::<i32>().unwrap_or(0_i32),
-                    password_md5: user_row.password_md5,
-                    salt: user_row.salt,
-                    email: user_row.email,
-                    register_datetime: user_row.register_datetime,
-                    rank: user_row.rank,
-                    allowed: user_row.allowed,
-                    latest_activity: user_row.latest_activity,
-                    silence_end: user_row.silence_end,
-                    silence_reason: user_row.silence_reason,
-                    password_version: user_row.password_version,
-                    privileges: Privileges::from_value(user_row.privileges),
-                    donor_expire: user_row.donor_expire,
-                    flags: user_row.flags,
-                    achievements_version: user_row.achievements_version,
-                    achievements_0: user_row.achievements_0,
-                    achievements_1: user_row.achievements_1,
-                    notes: user_row.notes.unwrap(),
-                    frozen: user_row.frozen,
-                    freezedate: user_row.freezedate,
-                    firstloginafterfrozen: user_row.firstloginafterfrozen,
-                    bypass_hwid: user_row.bypass_hwid,
-                    ban_reason: user_row.ban_reason,
-                    utc_offset: offset,
-                    country: country,
-                    geoloc: geoloc,
-                    bancho_priv: BanchoPrivileges::from_privileges(user_row.privileges),
-                    long: 0.0, // set later in login
-                    lat: 0.0,  // set later in login
-                    action: Action::Idle,
-                    info_text: "".to_string(),
-                    map_md5: "".to_string(),
-                    mods: Mods::NOMOD,
-                    current_mode: Mode::std,
-                    map_id: 0,
-                    token: token.to_string(),
-                    queue: PacketQueue::new(),
-                    stats: stats_vec,
-                    friends: friends_vec,
-                    spectating: None,
-                    spectators: Vec::new(),
-                    channels: HashMap::new(),
-                });
-            }
-            _ => return None,
+        let user_row = match user_row {
+            Some(user_row) => user_row,
+            _ => return Ok(None),
+        };
+
+        let country = sqlx::query!("select country from users_stats where id = ?", user_row.id)
+            .fetch_one(db.get().unwrap())
+            .await?
+            .country;
+
+        let friend_rows = sqlx::query!(
+            "select user2 from users_relationships where user1 = ?",
+            user_row.id
+        )
+        .fetch_all(db.get().unwrap())
+        .await?;
+
+        let friends_vec = friend_rows.iter().map(|v| v.user2).collect::<Vec<i32>>();
+
+        let geoloc =
+            CountryCodes::from_str(&country.to_uppercase()).unwrap_or(CountryCodes::XX) as u8;
+
+        let mut stats_vec: Vec<Stats> = Vec::new();
+        for mode in Mode::iter() {
+            stats_vec.push(Stats::for_mode(mode, user_row.id).await?);
+        }
+
+        return Ok(Some(Self {
+            id: user_row.id,
+            osuver: osu_ver.to_string(),
+            username: user_row.username,
+            username_safe: user_row.username_safe,
+            ban_datetime: user_row.ban_datetime.parse::<i32>().unwrap_or(0_i32),
+            password_md5: user_row.password_md5,
+            salt: user_row.salt,
+            email: user_row.email,
+            register_datetime: user_row.register_datetime,
+            rank: user_row.rank,
+            allowed: user_row.allowed,
+            latest_activity: user_row.latest_activity,
+            silence_end: user_row.silence_end,
+            silence_reason: user_row.silence_reason,
+            password_version: user_row.password_version,
+            privileges: Privileges::from_value(user_row.privileges),
+            donor_expire: user_row.donor_expire,
+            flags: user_row.flags,
+            achievements_version: user_row.achievements_version,
+            achievements_0: user_row.achievements_0,
+            achievements_1: user_row.achievements_1,
+            notes: user_row.notes.unwrap_or_default(),
+            frozen: user_row.frozen,
+            freezedate: user_row.freezedate,
+            firstloginafterfrozen: user_row.firstloginafterfrozen,
+            bypass_hwid: user_row.bypass_hwid,
+            ban_reason: user_row.ban_reason,
+            utc_offset: offset,
+            osu_md5: "".to_string(), // set later in login
+            country: country,
+            geoloc: geoloc,
+            bancho_priv: BanchoPrivileges::from_privileges(user_row.privileges),
+            long: 0.0, // set later in login
+            lat: 0.0,  // set later in login
+            action: Action::Idle,
+            info_text: "".to_string(),
+            map_md5: "".to_string(),
+            mods: Mods::NOMOD,
+            current_mode: Mode::std,
+            map_id: 0,
+            token: token.to_string(),
+            queue: Arc::new(PacketQueue::new()),
+            last_request: Instant::now(),
+            kicked: false,
+            stats: stats_vec,
+            presence: Arc::new(Presence::new(friends_vec)),
+            snapshot: Arc::new(Snapshot::new()),
+            spectating: Arc::new(Spectating::new()),
+            channels: HashMap::new(),
+        }));
+    }
+
+    // A normal, unrestricted user that isn't backed by the database,
+    // for driving the dispatcher outside of the server (rosu-replay, fuzzing).
+    pub fn stub(user_id: i32, username: &str) -> Self {
+        let privileges = Privileges::USER_PUBLIC | Privileges::USER_NORMAL;
+
+        return Self {
+            id: user_id,
+            osuver: "".to_string(),
+            username: username.to_string(),
+            username_safe: username.to_lowercase().replace(" ", "_"),
+            ban_datetime: 0,
+            password_md5: "".to_string(),
+            salt: "".to_string(),
+            email: "".to_string(),
+            register_datetime: 0,
+            rank: 1,
+            allowed: 1,
+            latest_activity: 0,
+            silence_end: 0,
+            silence_reason: "".to_string(),
+            password_version: 1,
+            privileges: privileges,
+            donor_expire: 0,
+            flags: 0,
+            achievements_version: 4,
+            achievements_0: 1,
+            achievements_1: 1,
+            notes: "".to_string(),
+            frozen: 0,
+            freezedate: 0,
+            firstloginafterfrozen: 0,
+            bypass_hwid: 0,
+            ban_reason: "".to_string(),
+            utc_offset: 0,
+            osu_md5: "".to_string(),
+            country: "XX".to_string(),
+            geoloc: CountryCodes::XX as u8,
+            bancho_priv: BanchoPrivileges::from_privileges(privileges.bits()),
+            long: 0.0,
+            lat: 0.0,
+            action: Action::Idle,
+            info_text: "".to_string(),
+            map_md5: "".to_string(),
+            mods: Mods::NOMOD,
+            current_mode: Mode::std,
+            map_id: 0,
+            token: Uuid::new_v4().to_string(),
+            queue: Arc::new(PacketQueue::new()),
+            last_request: Instant::now(),
+            kicked: false,
+            stats: Mode::iter().map(|_| Stats::default()).collect(),
+            presence: Arc::new(Presence::new(Vec::new())),
+            snapshot: Arc::new(Snapshot::new()),
+            spectating: Arc::new(Spectating::new()),
+            channels: HashMap::new(),
         };
     }
 
-    pub async fn enqueue(&self, bytes: Vec<u8>) {
+    pub async fn enqueue<B: Into<Bytes>>(&self, bytes: B) {
         self.queue.enqueue(bytes).await;
     }
 
-    pub async fn dequeue(&self) -> Vec<u8> {
+    pub async fn dequeue(&self) -> Bytes {
         return self.queue.dequeue().await;
     }
 
@@ -185,80 +270,155 @@ impl User {
         return self.privileges & Privileges::USER_PUBLIC < Privileges::USER_PUBLIC;
     }
 
+    // anyone with panel access counts as staff, used for maintenance bypass
+    pub fn staff(&self) -> bool {
+        return self.privileges.contains(Privileges::ADMIN_ACCESS_RAP);
+    }
+
     pub async fn add_friend(&mut self, target: i32) {
-        self.friends.push(target);
+        if !self.presence.add_friend(target) {
+            return;
+        }
 
-        sqlx::query("INSERT INTO users_relationships (user1, user2) VALUES (?, ?)")
+        // written straight away, a relogin or takeover reloads friends from the database
+        let result = sqlx::query("INSERT INTO users_relationships (user1, user2) VALUES (?, ?)")
             .bind(self.id)
             .bind(target)
             .execute(db.get().unwrap())
-            .await
-            .unwrap();
+            .await;
+
+        if let Err(e) = result {
+            error!(user_id = self.id, target, error = %e, "failed to add friend");
+            self.presence.remove_friend(target);
+        }
     }
 
     pub async fn remove_friend(&mut self, target: i32) {
-        let user_index = self.friends.iter().position(|x| *x == target).unwrap();
-        self.friends.remove(user_index);
+        if !self.presence.remove_friend(target) {
+            return;
+        }
 
-        sqlx::query("DELETE FROM users_relationships WHERE user1 = ? AND user2 = ?")
+        let result = sqlx::query("DELETE FROM users_relationships WHERE user1 = ? AND user2 = ?")
             .bind(self.id)
             .bind(target)
             .execute(db.get().unwrap())
-            .await
-            .unwrap();
+            .await;
+
+        if let Err(e) = result {
+            error!(user_id = self.id, target, error = %e, "failed to remove friend");
+            self.presence.add_friend(target);
+        }
     }
 
     pub async fn logout(&mut self) {
-        players.remove(self.id).await;
+        let spectators = self.end_session().await;
+
+        // the logout packet also stops their spectators' clients
+        if !self.restricted() {
+            cluster::broadcast(handlers::logout(self.id)).await;
+        } else {
+            cluster::enqueue_to(&spectators, handlers::logout(self.id)).await;
+        }
+
+        cluster::remove_session(self).await;
+    }
+
+    // Everything a logout does besides telling anyone, also used for a session that's
+    // being replaced. returns the spectators they had, who are left without a host.
+    pub async fn end_session(&mut self) -> Vec<i32> {
+        players.remove(self.id, &self.token);
 
         for channel in self.channels.values() {
             channel.remove_user(self.id).await;
         }
 
-        if !self.restricted() {
-            players.enqueue(handlers::logout(self.id)).await;
-        }
+        self.stop_spectating().await;
+        recordings.finish(self.id).await;
+        captures.close(self.id);
+
+        let spectators = self.spectating.take_spectators();
+        spectating::host_left(self.id, spectators.clone()).await;
+
+        return spectators;
     }
 
-    pub async fn add_spectator(&mut self, user: &mut User) {
-        let join_packet = handlers::spectator_joined(user.id);
+    // Shows the user why they're being kicked and stops their client,
+    // they're logged out once they next poll and receive this.
+    pub async fn kick(&mut self, reason: &str) {
+        let mut packet_bytes = handlers::notification(reason);
+        packet_bytes.extend(handlers::user_id(-1)); // client treats this as a failed login
 
-        // check, optionally create, and join spec channel
+        self.enqueue(packet_bytes).await;
+        self.kicked = true;
 
-        for uid in &self.spectators {
-            let u = players.get_id(*uid).await.unwrap();
-            let _user = u.read().await;
+        info!(user_id = self.id, username = %self.username, reason, "kicked");
+    }
 
-            _user.enqueue(join_packet.clone()).await;
-            user.enqueue(handlers::spectator_joined(_user.id)).await;
+    // Starts spectating `host_id`, leaving whoever they were spectating before.
+    // only the shared spectating state is touched, so no other user is ever locked.
+    pub async fn start_spectating(&self, host_id: i32) {
+        if host_id == self.id || self.spectating.host() == Some(host_id) {
+            return;
         }
 
-        self.spectators.push(user.id);
-        user.spectating = Some(self.id);
+        self.stop_spectating().await;
 
-        self.enqueue(handlers::host_spectator_joined(user.id)).await;
-        println!("{} started spectating {}", user.username, self.username);
-    }
+        // a host on another instance is joined over there, as long as they're online somewhere
+        let joined = match players.spectating(host_id) {
+            Some(_) => spectating::join(host_id, self.id).await,
+            _ if cluster::is_online(host_id).await => {
+                cluster::relay(Event::StartSpectating {
+                    host_id: host_id,
+                    spectator_id: self.id,
+                })
+                .await
+            }
+            _ => false,
+        };
 
-    pub async fn remove_spectator(&mut self, user: &mut User) {
-        let user_index = self.spectators.iter().position(|x| *x == user.id).unwrap();
-        self.spectators.remove(user_index);
-        user.spectating = None;
+        if joined {
+            self.spectating.set_host(Some(host_id));
+        }
 
-        // leave spec channel (update channel info etc.) &
-        // check if remaining spectators == 0 and remove self if so
+        // check, optionally create, and join spec channel
+    }
+
+    pub async fn stop_spectating(&self) {
+        let host_id = match self.spectating.set_host(None) {
+            Some(host_id) => host_id,
+            _ => return,
+        };
 
-        let leave_packet = handlers::spectator_left(user.id);
-        for uid in &self.spectators {
-            // this will need to be in the else clause of channel deletion once it exists
-            let u = players.get_id(*uid).await.unwrap();
-            let _user = u.read().await;
+        // leave spec channel (update channel info etc.)
 
-            _user.enqueue(leave_packet.clone()).await;
+        match players.spectating(host_id) {
+            Some(_) => spectating::leave(host_id, self.id).await,
+            _ => {
+                cluster::relay(Event::StopSpectating {
+                    host_id: host_id,
+                    spectator_id: self.id,
+                })
+                .await;
+            }
         }
+    }
+
+    pub async fn cant_spectate(&self) {
+        let host_id = match self.spectating.host() {
+            Some(host_id) => host_id,
+            _ => return,
+        };
 
-        self.enqueue(handlers::host_spectator_left(user.id)).await;
-        println!("{} stopped spectating {}", user.username, self.username);
+        match players.spectating(host_id) {
+            Some(_) => spectating::cant_spectate(host_id, self.id).await,
+            _ => {
+                cluster::relay(Event::CantSpectate {
+                    host_id: host_id,
+                    spectator_id: self.id,
+                })
+                .await;
+            }
+        }
     }
 
     // generic function to do all actions after a confirmed restriction
@@ -276,5 +436,7 @@ impl User {
                 .fetch_one(db.get().unwrap())
                 .await
                 .unwrap();
+
+        self.snapshot.update(self);
     }
 }
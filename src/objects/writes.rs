@@ -0,0 +1,135 @@
+use dashmap::DashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::db;
+
+pub fn unix_time() -> i32 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i32;
+}
+
+// cuts a string down to fit a varchar column
+#[inline(always)]
+fn truncate(value: &str, max_chars: usize) -> String {
+    return value.chars().take(max_chars).collect();
+}
+
+// A database write that doesn't need to happen before we respond to the client.
+#[derive(Debug)]
+pub enum PendingWrite {
+    ErrorReport {
+        user_id: i32,
+        timestamp: i32,
+        traceback: String,
+        config: String,
+        osu_ver: String,
+        osu_hash: String,
+    },
+    LastFmFlag {
+        user_id: i32,
+        timestamp: i32,
+        flags: i32,
+        text: String,
+    },
+}
+
+// Writes queued up to be flushed in the background, so request handling
+// never waits on the database for them. flushed periodically and on shutdown.
+pub struct WriteQueue {
+    writes: Mutex<Vec<PendingWrite>>,
+    activity: DashMap<i32, i32>, // user id -> latest activity, only the newest one matters
+    flushing: Mutex<()>,         // keeps overlapping flushes from reordering writes
+}
+
+impl WriteQueue {
+    pub fn new() -> Self {
+        return Self {
+            writes: Mutex::new(Vec::new()),
+            activity: DashMap::new(),
+            flushing: Mutex::new(()),
+        };
+    }
+
+    pub async fn push(&self, write: PendingWrite) {
+        self.writes.lock().await.push(write);
+    }
+
+    // Marks the user as active now, their `latest_activity` is updated on the next flush.
+    pub fn touch(&self, user_id: i32) {
+        self.activity.insert(user_id, unix_time());
+    }
+
+    pub async fn flush(&self) {
+        let _guard = self.flushing.lock().await;
+        let pool = db.get().unwrap();
+
+        let writes = std::mem::take(&mut *self.writes.lock().await);
+        for write in writes {
+            let result = match &write {
+                PendingWrite::ErrorReport {
+                    user_id,
+                    timestamp,
+                    traceback,
+                    config,
+                    osu_ver,
+                    osu_hash,
+                } => {
+                    sqlx::query(
+                        "INSERT INTO client_err_logs (user_id, timestamp, traceback, config, osu_ver, osu_hash) \
+                         VALUES (?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind(user_id)
+                    .bind(timestamp)
+                    .bind(truncate(traceback, 1024))
+                    .bind(truncate(config, 2048))
+                    .bind(truncate(osu_ver, 12))
+                    .bind(truncate(osu_hash, 32))
+                    .execute(pool)
+                    .await
+                }
+                PendingWrite::LastFmFlag {
+                    user_id,
+                    timestamp,
+                    flags,
+                    text,
+                } => {
+                    sqlx::query(
+                        "INSERT INTO lastfm_flags (user_id, timestamp, flag_enum, flag_text) VALUES (?, ?, ?, ?)",
+                    )
+                    .bind(user_id)
+                    .bind(timestamp)
+                    .bind(flags)
+                    .bind(truncate(text, 512))
+                    .execute(pool)
+                    .await
+                }
+            };
+
+            if let Err(e) = result {
+                error!(?write, error = %e, "failed to flush write");
+            }
+        }
+
+        let user_ids = self.activity.iter().map(|e| *e.key()).collect::<Vec<i32>>();
+        for user_id in user_ids {
+            let latest_activity = match self.activity.remove(&user_id) {
+                Some((_, latest_activity)) => latest_activity,
+                _ => continue,
+            };
+
+            let result = sqlx::query("UPDATE users SET latest_activity = ? WHERE id = ?")
+                .bind(latest_activity)
+                .bind(user_id)
+                .execute(pool)
+                .await;
+
+            if let Err(e) = result {
+                error!(user_id, error = %e, "failed to update activity");
+            }
+        }
+    }
+}
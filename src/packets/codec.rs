@@ -0,0 +1,200 @@
+use crate::constants::action::Action;
+use crate::constants::packets::Packets;
+use crate::packets::reader::{DecodeResult, Reader};
+use crate::packets::writer::{write_osu_string, PacketWriter};
+
+// Types that can be written into a bancho packet body.
+pub trait BanchoEncode {
+    fn encode(&self, buf: &mut Vec<u8>);
+}
+
+// Types that can be read back out of a bancho packet body.
+pub trait BanchoDecode: Sized {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self>;
+}
+
+// A full packet body, tied to the id it's sent or received with.
+pub trait BanchoPacket: BanchoEncode + BanchoDecode {
+    const ID: Packets;
+
+    /// Serialises the packet including its header.
+    fn serialise(&self) -> Vec<u8> {
+        let mut writer = PacketWriter::new(Self::ID);
+        writer += self;
+        return writer.serialise();
+    }
+}
+
+macro_rules! impl_primitive {
+    ($name: ident) => {
+        impl BanchoEncode for $name {
+            #[inline(always)]
+            fn encode(&self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl BanchoDecode for $name {
+            #[inline(always)]
+            fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+                reader.read_int()
+            }
+        }
+    };
+}
+
+impl_primitive!(u8);
+impl_primitive!(i8);
+impl_primitive!(u16);
+impl_primitive!(i16);
+impl_primitive!(u32);
+impl_primitive!(i32);
+impl_primitive!(u64);
+impl_primitive!(i64);
+
+impl BanchoEncode for f32 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl BanchoDecode for f32 {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        reader.read_f32()
+    }
+}
+
+impl BanchoEncode for f64 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl BanchoDecode for f64 {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        reader.read_f64()
+    }
+}
+
+impl BanchoEncode for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+}
+
+impl BanchoDecode for bool {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        Ok(reader.read_int::<u8>()? != 0)
+    }
+}
+
+impl BanchoEncode for str {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(write_osu_string(self));
+    }
+}
+
+impl BanchoEncode for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.as_str().encode(buf);
+    }
+}
+
+impl BanchoDecode for String {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        reader.read_str()
+    }
+}
+
+// i32 lists are the only lists in the protocol with a u16 length prefix,
+// the rest are handled by hand where they're used.
+impl BanchoEncode for Vec<i32> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u16).encode(buf);
+        for elem in self {
+            elem.encode(buf);
+        }
+    }
+}
+
+impl BanchoDecode for Vec<i32> {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        reader.read_i32_list()
+    }
+}
+
+impl<T: BanchoEncode + ?Sized> BanchoEncode for &T {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (**self).encode(buf);
+    }
+}
+
+impl BanchoEncode for Packets {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as i16).encode(buf);
+    }
+}
+
+impl BanchoEncode for Action {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as u8).encode(buf);
+    }
+}
+
+// The rest of the packet body, passed through untouched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawBytes(pub Vec<u8>);
+
+impl BanchoEncode for RawBytes {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
+
+impl BanchoDecode for RawBytes {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        Ok(Self(reader.read_raw()?))
+    }
+}
+
+// Declares a struct whose fields are encoded/decoded in order.
+// given a packet id, it also becomes a full BanchoPacket.
+macro_rules! bancho_struct {
+    (
+        $(#[$attr:meta])*
+        $name:ident { $( $field:ident : $t:ty ),* $(,)? }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct $name {
+            $( pub $field: $t, )*
+        }
+
+        impl crate::packets::codec::BanchoEncode for $name {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                $( crate::packets::codec::BanchoEncode::encode(&self.$field, buf); )*
+            }
+        }
+
+        impl crate::packets::codec::BanchoDecode for $name {
+            fn decode(reader: &mut crate::packets::reader::Reader) -> crate::packets::reader::DecodeResult<Self> {
+                Ok(Self {
+                    $( $field: reader.read::<$t>()?, )*
+                })
+            }
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        $packet:ident => $name:ident { $( $field:ident : $t:ty ),* $(,)? }
+    ) => {
+        bancho_struct! {
+            $(#[$attr])*
+            $name { $( $field : $t ),* }
+        }
+
+        impl crate::packets::codec::BanchoPacket for $name {
+            const ID: crate::constants::packets::Packets = crate::constants::packets::Packets::$packet;
+        }
+    };
+}
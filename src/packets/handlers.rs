@@ -1,163 +1,165 @@
+use crate::cluster;
 use crate::constants::action::Action;
+use crate::constants::grade::Grade;
 use crate::constants::mode::Mode;
 use crate::constants::packets::Packets;
+use crate::constants::presence::PresenceFilter;
+use crate::constants::replay::ReplayAction;
+use crate::objects::beatmap::{self, Beatmap};
 use crate::objects::mods::Mods;
 use crate::objects::user::User;
-use crate::packets::reader::Reader;
-use crate::packets::writer::PacketWriter;
-use crate::players;
+use crate::objects::writes::{self, PendingWrite};
+use crate::packets::codec::BanchoPacket;
+use crate::packets::reader::{DecodeResult, Reader};
+use crate::packets::structs::{cho, osu, BeatmapInfoList, Message, ReplayFrameBundle};
+use crate::{anticheat_pipeline, config, error_reports, pending_writes, players, recordings};
 
 use futures::future::{BoxFuture, FutureExt};
 use num_traits::FromPrimitive;
-use std::collections::HashMap;
-use tokio::sync::RwLockReadGuard;
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
 
 #[inline(always)]
 pub fn user_id(user_id: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_USER_ID);
-    writer += user_id;
-    return writer.serialise();
+    return cho::UserId { user_id: user_id }.serialise();
 }
 
 #[inline(always)]
 pub fn notification(notification: &str) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_NOTIFICATION);
-    writer += notification;
-    return writer.serialise();
+    return cho::Notification {
+        message: notification.to_string(),
+    }
+    .serialise();
 }
 
 #[inline(always)]
 pub fn protocol_version(version: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_PROTOCOL_VERSION);
-    writer += version;
-    return writer.serialise();
+    return cho::ProtocolVersion { version: version }.serialise();
 }
 
 #[inline(always)]
 pub fn bancho_privileges(privs: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_PRIVILEGES);
-    writer += privs;
-    return writer.serialise();
+    return cho::Privileges { privileges: privs }.serialise();
 }
 
 #[inline(always)]
 pub fn channel_info_end() -> Vec<u8> {
-    // lol this is so stupid
-    let mut writer = PacketWriter::new(Packets::CHO_CHANNEL_INFO_END);
-    return writer.serialise();
+    return cho::ChannelInfoEnd {}.serialise();
 }
 
 #[inline(always)]
 pub fn main_menu_icon(icon: &str, link: &str) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_MAIN_MENU_ICON);
-    writer += format!("{}|{}", icon, link).as_str();
-    return writer.serialise();
+    return cho::MainMenuIcon {
+        icon: format!("{}|{}", icon, link),
+    }
+    .serialise();
 }
 
 #[inline(always)]
 pub fn friends_list(user: &User) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_FRIENDS_LIST);
-    writer += &user.friends;
-    return writer.serialise();
+    return cho::FriendsList {
+        user_ids: user.presence.friends(),
+    }
+    .serialise();
 }
 
 #[inline(always)]
 pub fn silence_end(silence_end: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_SILENCE_END);
-    writer += silence_end;
-    return writer.serialise();
+    return cho::SilenceEnd {
+        seconds: silence_end,
+    }
+    .serialise();
 }
 
 #[inline(always)]
 pub fn user_presence(user: &User) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_USER_PRESENCE);
-
-    writer += user.id;
-    writer += &user.username;
-    writer += (user.utc_offset + 24) as u8;
-    writer += user.geoloc;
-    writer += user.bancho_priv.value() as u8 | ((user.current_mode as u8) << 5);
-    writer += user.long;
-    writer += user.lat;
-    writer += 0 as i32; // user rank (hardcode for now)
-
-    return writer.serialise();
+    return cho::UserPresence {
+        user_id: user.id,
+        username: user.username.clone(),
+        utc_offset: (user.utc_offset + 24) as u8,
+        country: user.geoloc,
+        privileges: user.bancho_priv.value() as u8 | ((user.current_mode as u8) << 5),
+        longitude: user.long,
+        latitude: user.lat,
+        rank: 0, // user rank (hardcode for now)
+    }
+    .serialise();
 }
 
 #[inline(always)]
-pub fn user_stats(user: &User) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_USER_STATS);
+pub fn user_presence_single(user_id: i32) -> Vec<u8> {
+    return cho::UserPresenceSingle { user_id: user_id }.serialise();
+}
 
-    writer += user.id;
-    writer += user.action as u8;
-    writer += &user.info_text;
-    writer += &user.map_md5;
-    writer += user.mods.bits() as i32;
-    writer += user.current_mode as u8;
-    writer += user.map_id;
+#[inline(always)]
+pub fn user_presence_bundle(user_ids: Vec<i32>) -> Vec<u8> {
+    return cho::UserPresenceBundle { user_ids: user_ids }.serialise();
+}
 
+#[inline(always)]
+pub fn user_stats(user: &User) -> Vec<u8> {
     let stats = &user.stats[user.current_mode as usize];
 
-    writer += stats.ranked_score as i64;
-    writer += stats.accuracy / 100.0 as f32;
-    writer += stats.playcount as i32;
-    writer += stats.total_score as i64;
-    writer += 0 as i32; // global rank
-    writer += stats.pp as i16;
-
-    return writer.serialise();
+    return cho::UserStats {
+        user_id: user.id,
+        action: user.action as u8,
+        info_text: user.info_text.clone(),
+        map_md5: user.map_md5.clone(),
+        mods: user.mods.bits(),
+        mode: user.current_mode as u8,
+        map_id: user.map_id,
+        ranked_score: stats.ranked_score as i64,
+        accuracy: stats.accuracy / 100.0,
+        playcount: stats.playcount,
+        total_score: stats.total_score as i64,
+        rank: 0, // global rank
+        pp: stats.pp as i16,
+    }
+    .serialise();
 }
 
 #[inline(always)]
 pub fn server_restart(time: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_RESTART);
-    writer += time;
-    return writer.serialise();
+    return cho::Restart { delay_ms: time }.serialise();
 }
 
 #[inline(always)]
 pub fn logout(user_id: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_USER_LOGOUT);
-
-    writer += user_id;
-    writer += 0 as u8; // logout timeout?
-
-    return writer.serialise();
+    return cho::UserLogout {
+        user_id: user_id,
+        reserved: 0, // logout timeout?
+    }
+    .serialise();
 }
 
 #[inline(always)]
 pub fn spectator_joined(user_id: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_FELLOW_SPECTATOR_JOINED);
-    writer += user_id;
-    return writer.serialise();
+    return cho::FellowSpectatorJoined { user_id: user_id }.serialise();
 }
 
 #[inline(always)]
 pub fn host_spectator_joined(user_id: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_SPECTATOR_JOINED);
-    writer += user_id;
-    return writer.serialise();
+    return cho::SpectatorJoined { user_id: user_id }.serialise();
 }
 
 #[inline(always)]
 pub fn spectator_left(user_id: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_FELLOW_SPECTATOR_LEFT);
-    writer += user_id;
-    return writer.serialise();
+    return cho::FellowSpectatorLeft { user_id: user_id }.serialise();
 }
 
 #[inline(always)]
 pub fn host_spectator_left(user_id: i32) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_SPECTATOR_LEFT);
-    writer += user_id;
-    return writer.serialise();
+    return cho::SpectatorLeft { user_id: user_id }.serialise();
 }
 
 #[inline(always)]
-pub fn spectate_frames(frames: Vec<u8>) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::OSU_SPECTATE_FRAMES);
-    writer += frames;
-    return writer.serialise();
+pub fn spectator_cant_spectate(user_id: i32) -> Vec<u8> {
+    return cho::SpectatorCantSpectate { user_id: user_id }.serialise();
+}
+
+#[inline(always)]
+pub fn spectate_frames(bundle: ReplayFrameBundle) -> Vec<u8> {
+    return cho::SpectateFrames { bundle: bundle }.serialise();
 }
 
 #[inline(always)]
@@ -167,19 +169,20 @@ pub fn channel_message(
     content: String,
     target_name: String,
 ) -> Vec<u8> {
-    let mut writer = PacketWriter::new(Packets::CHO_SEND_MESSAGE);
-
-    writer += src_name;
-    writer += content;
-    writer += target_name;
-    writer += src_id;
-
-    return writer.serialise();
+    return cho::SendMessage {
+        message: Message {
+            sender: src_name,
+            content: content,
+            recipient: target_name,
+            sender_id: src_id,
+        },
+    }
+    .serialise();
 }
 
 pub type HandlerHashMap = HashMap<
     Packets,
-    for<'lt> fn(user: &'lt mut User, reader: &'lt mut Reader) -> BoxFuture<'lt, bool>,
+    for<'lt> fn(user: &'lt mut User, reader: &'lt mut Reader) -> BoxFuture<'lt, DecodeResult<()>>,
 >;
 
 macro_rules! register_packets {(
@@ -188,7 +191,7 @@ macro_rules! register_packets {(
      $( #[$attr:meta] )*
         $pub:vis
         async
-        fn $fname:ident ($user:ident : & $('_)? mut User, $reader:ident : & $('_)? mut Reader) -> bool
+        fn $fname:ident ($user:ident : & $('_)? mut User, $reader:ident : & $('_)? mut Reader) -> DecodeResult<()>
         $body:block
     )*
 ) => (
@@ -198,7 +201,7 @@ macro_rules! register_packets {(
         fn $fname<'lt> (
             $user : &'lt mut User,
             $reader : &'lt mut Reader,
-        ) -> BoxFuture<'lt, bool>
+        ) -> BoxFuture<'lt, DecodeResult<()>>
         {
             return FutureExt::boxed(async move {
                 let _ = (&$user, &$reader);
@@ -229,181 +232,323 @@ macro_rules! register_packets {(
 // read handlers
 register_packets! {
     // format for attribute: #[packet(packet_enum, allowed while restricted)]
-    // each function returns a bool of whether or not the reader buffer should be incremented
+    // each function gets a reader over just its packet's body, and returns
+    // a decode error if the packet was malformed (the dispatcher skips it)
 
     #[packet(Packets::OSU_PING, true)]
     #[inline(always)]
-    pub async fn ping(user: &mut User, reader: &mut Reader) -> bool {
-        let mut writer = PacketWriter::new(Packets::CHO_PONG);
-        let pong = writer.serialise();
-
-        user.enqueue(pong).await;
-        return true;
+    pub async fn ping(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        user.enqueue(cho::Pong {}.serialise()).await;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_REQUEST_STATUS_UPDATE, true)]
     #[inline(always)]
-    pub async fn status_update(user: &mut User, reader: &mut Reader) -> bool {
+    pub async fn status_update(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
         user.enqueue(user_stats(user)).await;
-        return true;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_USER_STATS_REQUEST, true)]
     #[inline(always)]
-    pub async fn stats_request(user: &mut User, reader: &mut Reader) -> bool {
-        let user_ids = reader.read_i32_list();
-
-        for uid in user_ids {
-            match players.get_id(uid).await {
-                Some(u) => {
-                    if !u.read().await.restricted() {
-                        user.enqueue(user_presence(user)).await;
+    pub async fn stats_request(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::UserStatsRequest = reader.read()?;
+
+        let mut packet_bytes = Vec::new();
+        let mut remote_ids = Vec::new();
+        for uid in packet.user_ids {
+            // answered from their snapshot, so nobody else gets locked while we hold our own lock
+            match players.snapshot(uid) {
+                Some(snapshot) => {
+                    if uid == user.id || !snapshot.restricted() {
+                        packet_bytes.extend(snapshot.stats());
                     }
-                },
-                _ => (),
+                }
+                _ => remote_ids.push(uid),
             }
         }
+        packet_bytes.extend(cluster::stats(&remote_ids).await);
+
+        if !packet_bytes.is_empty() {
+            user.enqueue(packet_bytes).await;
+        }
 
-        return false;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_USER_PRESENCE_REQUEST, true)]
     #[inline(always)]
-    pub async fn presence_request(user: &mut User, reader: &mut Reader) -> bool {
-        let user_ids = reader.read_i32_list();
-
-        for uid in user_ids {
-            match players.get_id(uid).await {
-                Some(u) => {
-                    let _user: &RwLockReadGuard<'_, User> = &u.read().await;
-                    user.enqueue(user_presence(_user)).await;
-                },
-                _ => (),
+    pub async fn presence_request(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::UserPresenceRequest = reader.read()?;
+
+        let mut packet_bytes = Vec::new();
+        let mut remote_ids = Vec::new();
+        for uid in packet.user_ids {
+            if uid != user.id && !user.presence.wants(uid) {
+                continue;
             }
+
+            match players.snapshot(uid) {
+                Some(snapshot) => {
+                    if uid == user.id || !snapshot.restricted() {
+                        packet_bytes.extend(snapshot.presence());
+                    }
+                }
+                _ => remote_ids.push(uid),
+            }
+        }
+
+        packet_bytes.extend(cluster::presences(&remote_ids).await);
+        if !packet_bytes.is_empty() {
+            user.enqueue(packet_bytes).await;
         }
 
-        return false;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_USER_PRESENCE_REQUEST_ALL, true)]
     #[inline(always)]
-    pub async fn full_presence(user: &mut User, reader: &mut Reader) -> bool {
-        for u in players.players.lock().await.values() {
-            let _user = &u.read().await;
+    pub async fn full_presence(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let mut packet_bytes = user_presence(user);
+        for (uid, snapshot) in players.snapshots() {
+            if uid != user.id && !snapshot.restricted() && user.presence.wants(uid) {
+                packet_bytes.extend(snapshot.presence());
+            }
+        }
+
+        let mut remote_ids = cluster::remote_ids().await;
+        remote_ids.retain(|uid| user.presence.wants(*uid));
+        packet_bytes.extend(cluster::presences(&remote_ids).await);
+
+        user.enqueue(packet_bytes).await;
+
+        return Ok(());
+    }
+
+    #[packet(Packets::OSU_RECEIVE_UPDATES, true)]
+    #[inline(always)]
+    pub async fn receive_updates(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::ReceiveUpdates = reader.read()?;
+
+        match PresenceFilter::from_i32(packet.filter) {
+            Some(filter) => user.presence.set_filter(filter),
+            _ => warn!(filter = packet.filter, "unknown presence filter"),
+        }
+
+        // the instance that picks up their session next needs it too
+        cluster::store_session(user).await;
+
+        return Ok(());
+    }
+
+    #[packet(Packets::OSU_ERROR_REPORT, true)]
+    #[inline(always)]
+    pub async fn error_report(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::ErrorReport = reader.read()?;
+
+        // a client stuck in a crash loop would otherwise send one of these every login
+        if !error_reports.check(user.id) {
+            return Ok(());
+        }
+
+        pending_writes
+            .push(PendingWrite::ErrorReport {
+                user_id: user.id,
+                timestamp: writes::unix_time(),
+                traceback: packet.report.traceback,
+                config: packet.report.config,
+                osu_ver: user.osuver.clone(),
+                osu_hash: user.osu_md5.clone(),
+            })
+            .await;
+
+        info!("stored client error report");
+        return Ok(());
+    }
 
-            if !_user.restricted() {
-                user.enqueue(user_presence(_user)).await;
+    #[packet(Packets::OSU_BEATMAP_INFO_REQUEST, true)]
+    #[inline(always)]
+    pub async fn beatmap_info_request(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::BeatmapInfoRequest = reader.read()?;
+        let request = packet.data;
+
+        // whatever either lookup found is still sent if the other one fails
+        let mut maps = match Beatmap::from_filenames(&request.filenames).await {
+            Ok(maps) => maps,
+            Err(e) => {
+                warn!(error = %e, "failed to look up beatmaps by filename");
+                Vec::new()
             }
+        };
+
+        match Beatmap::from_ids(&request.ids).await {
+            Ok(id_maps) => maps.extend(id_maps),
+            Err(e) => warn!(error = %e, "failed to look up beatmaps by id"),
+        }
+
+        // a map asked for by both filename and id only gets one reply, the one with its filename index
+        let mut seen = HashSet::new();
+        maps.retain(|map| seen.insert(map.beatmap_id));
+
+        let md5s = maps.iter().map(|m| m.beatmap_md5.clone()).collect::<Vec<String>>();
+        let grades = beatmap::best_grades(user.id, user.current_mode, &md5s)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "failed to look up grades");
+                HashMap::new()
+            });
+
+        // the client matches replies up to its request by filename index
+        let indexes = request
+            .filenames
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.as_str(), index as i16))
+            .collect::<HashMap<&str, i16>>();
+
+        let mut infos = Vec::with_capacity(maps.len());
+        for map in &maps {
+            let index = map
+                .file_name
+                .as_deref()
+                .and_then(|name| indexes.get(name).copied())
+                .unwrap_or(-1);
+
+            let map_grades = grades
+                .get(&map.beatmap_md5)
+                .copied()
+                .unwrap_or([Grade::N; 4]);
+
+            infos.push(map.info(index, map_grades));
         }
 
-        return true;
+        user.enqueue(
+            cho::BeatmapInfoReply {
+                infos: BeatmapInfoList(infos),
+            }
+            .serialise(),
+        )
+        .await;
+
+        return Ok(());
     }
 
     #[packet(Packets::OSU_FRIEND_ADD, true)]
     #[inline(always)]
-    pub async fn add_friend(user: &mut User, reader: &mut Reader) -> bool {
-        let target: i32 = reader.read_int();
+    pub async fn add_friend(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::FriendAdd = reader.read()?;
 
-        if user.friends.contains(&target) {
-            return false;
+        if user.presence.is_friend(packet.user_id) {
+            return Ok(());
         }
 
-        user.add_friend(target).await;
+        user.add_friend(packet.user_id).await;
 
-        return false;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_FRIEND_REMOVE, true)]
     #[inline(always)]
-    pub async fn remove_friend(user: &mut User, reader: &mut Reader) -> bool {
-        let target: i32 = reader.read_int();
+    pub async fn remove_friend(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::FriendRemove = reader.read()?;
 
-        if !user.friends.contains(&target) {
-            return false;
+        if !user.presence.is_friend(packet.user_id) {
+            return Ok(());
         }
 
-        user.remove_friend(target).await;
+        user.remove_friend(packet.user_id).await;
 
-        return false;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_LOGOUT, true)]
     #[inline(always)]
-    pub async fn user_logout(user: &mut User, reader: &mut Reader) -> bool {
+    pub async fn user_logout(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
         user.logout().await;
 
-        println!("{} logged out", user.username);
+        info!("logged out");
 
-        return true;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_CHANGE_ACTION, true)]
     #[inline(always)]
-    pub async fn change_action(user: &mut User, reader: &mut Reader) -> bool {
-        let action_id: u8 = reader.read_int();
-        let action_info: String = reader.read_str();
-        let map_md5: String = reader.read_str();
-        let mods: u32 = reader.read_int();
-        let mode: u8 = reader.read_int();
-        let map_id: i32 = reader.read_int();
-
-        user.action = Action::from_u8(action_id).unwrap();
-        user.info_text = action_info;
-        user.map_md5 = map_md5;
-        user.mods = Mods::from_value(mods as i32);
-        user.current_mode = Mode::from_mods(mode as i32, mods as i32);
-        user.map_id = map_id;
+    pub async fn change_action(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::ChangeAction = reader.read()?;
+
+        user.action = Action::from_u8(packet.action).unwrap_or(Action::Unknown);
+        user.info_text = packet.info_text;
+        user.map_md5 = packet.map_md5;
+        user.mods = Mods::from_value(packet.mods as i32);
+        user.current_mode = Mode::from_mods(packet.mode as i32, packet.mods as i32);
+        user.map_id = packet.map_id;
+
+        user.snapshot.update(user);
+        anticheat_pipeline.status(user).await;
+
+        // the instance that picks up their session next needs it too
+        cluster::store_session(user).await;
 
         if !user.restricted() {
-            players.enqueue(user_stats(user)).await;
+            cluster::update_presence(user).await;
+            cluster::broadcast_presence(user.id, user_stats(user)).await;
         }
 
-        return false;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_START_SPECTATING, false)]
     #[inline(always)]
-    pub async fn start_spectating(user: &mut User, reader: &mut Reader) -> bool {
-        let target: i32 = reader.read_int();
+    pub async fn start_spectating(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::StartSpectating = reader.read()?;
+        let target = packet.target_id;
 
-        if target == 999 || target == 1 { // ignore the bot
-            return false;
+        if config.get().unwrap().is_bot(target) {
+            return Ok(());
         }
 
-        let u = players.get_id(target).await.unwrap();
-        let mut _user = u.write().await;
-        _user.add_spectator(user).await;
+        user.start_spectating(target).await;
 
-        return false;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_STOP_SPECTATING, false)]
     #[inline(always)]
-    pub async fn stop_spectating(user: &mut User, reader: &mut Reader) -> bool  {
-        if user.spectating == None {
-            return true;
-        }
+    pub async fn stop_spectating(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        user.stop_spectating().await;
+
+        return Ok(());
+    }
 
-        let u = players.get_id(user.spectating.unwrap()).await.unwrap();
-        let mut _user = u.write().await;
-        _user.remove_spectator(user).await;
+    #[packet(Packets::OSU_CANT_SPECTATE, false)]
+    #[inline(always)]
+    pub async fn cant_spectate(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        user.cant_spectate().await;
 
-        return true;
+        return Ok(());
     }
 
     #[packet(Packets::OSU_SPECTATE_FRAMES, false)]
     #[inline(always)]
-    pub async fn user_spectate_frames(user: &mut User, reader: &mut Reader) -> bool {
-        let frames = reader.read_raw();
+    pub async fn user_spectate_frames(user: &mut User, reader: &mut Reader) -> DecodeResult<()> {
+        let packet: osu::SpectateFrames = reader.read()?;
+
+        if ReplayAction::from_u8(packet.bundle.action).is_none() {
+            warn!(action = packet.bundle.action, "unknown replay action");
+            return Ok(());
+        }
 
-        let frames_packet = spectate_frames(frames);
-        for u in players.players.lock().await.values() {
-            let _user = u.read().await;
+        anticheat_pipeline.frames(user, &packet.bundle).await;
+        recordings.record(user, &packet.bundle).await;
 
-            _user.enqueue(frames_packet.clone()).await;
+        let spectators = user.spectating.spectators();
+        if spectators.is_empty() {
+            return Ok(());
         }
 
-        return false;
+        cluster::enqueue_to(&spectators, spectate_frames(packet.bundle)).await;
+
+        return Ok(());
     }
 
     // TODO: channels & msgs (left as realistik wants to do them)
@@ -1,3 +1,9 @@
+#[macro_use]
+pub mod codec;
 pub mod handlers;
 pub mod reader;
+pub mod structs;
 pub mod writer;
+
+#[cfg(test)]
+mod tests;
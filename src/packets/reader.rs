@@ -1,4 +1,35 @@
 use std::convert::TryInto;
+use std::fmt;
+
+use crate::packets::codec::BanchoDecode;
+
+// Everything that can go wrong while decoding a request body.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    Truncated { needed: usize, remaining: usize },
+    BadStringMarker(u8),
+    OverlongUleb128,
+    InvalidUtf8,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { needed, remaining } => write!(
+                f,
+                "truncated packet (needed {} bytes, {} remaining)",
+                needed, remaining
+            ),
+            Self::BadStringMarker(marker) => write!(f, "bad string marker {:#04x}", marker),
+            Self::OverlongUleb128 => write!(f, "uleb128 longer than 32 bits"),
+            Self::InvalidUtf8 => write!(f, "string is not valid utf-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
 
 pub struct Reader {
     buf: Vec<u8>,
@@ -13,35 +44,76 @@ impl Reader {
         }
     }
 
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.offset)
+    }
+
     pub fn incr_offset(&mut self, amount: usize) {
         self.offset += amount;
     }
 
+    /// Moves the reader to an absolute offset, e.g. the end of a packet that failed to decode.
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset;
+    }
+
+    /// Takes the next `len` bytes, failing if the buffer doesn't have them.
+    fn take(&mut self, len: usize) -> DecodeResult<&[u8]> {
+        if self.remaining() < len {
+            return Err(DecodeError::Truncated {
+                needed: len,
+                remaining: self.remaining(),
+            });
+        }
+
+        let start = self.offset;
+        self.incr_offset(len);
+        Ok(&self.buf[start..start + len])
+    }
+
+    /// Decodes any bancho type `T` from the buffer.
+    pub fn read<T: BanchoDecode>(&mut self) -> DecodeResult<T> {
+        T::decode(self)
+    }
+
+    /// Splits off the next `len` bytes as their own reader, so a packet
+    /// handler can never read past the end of its packet.
+    pub fn read_packet(&mut self, len: usize) -> DecodeResult<Reader> {
+        Ok(Reader::new(self.take(len)?.to_vec()))
+    }
+
     /// Reads a primitive type `T` from the buffer.
-    pub fn read_int<T: Readable>(&mut self) -> T {
-        let value = T::from_le_bytes(&self.buf[self.offset..self.offset + T::SIZE]);
-        self.incr_offset(T::SIZE);
-        return value;
+    pub fn read_int<T: Readable>(&mut self) -> DecodeResult<T> {
+        let bytes = self.take(T::SIZE)?;
+        Ok(T::from_le_bytes(bytes))
     }
 
     // Maybe this should be part of read_int. Would be easily doable.
-    pub fn read_f32(&mut self) -> f32 {
-        let val = f32::from_le_bytes(
-            self.buf[self.offset..self.offset + 4]
-                .try_into()
-                .expect("Should never happen."),
-        );
-        self.incr_offset(4);
-        val
+    pub fn read_f32(&mut self) -> DecodeResult<f32> {
+        let bytes = self.take(4)?;
+        Ok(f32::from_le_bytes(
+            bytes.try_into().expect("Should never happen."),
+        ))
+    }
+
+    pub fn read_f64(&mut self) -> DecodeResult<f64> {
+        let bytes = self.take(8)?;
+        Ok(f64::from_le_bytes(
+            bytes.try_into().expect("Should never happen."),
+        ))
     }
 
     /// Reads a 128bit unsigned LEB integer from the buffer.
-    pub fn read_uleb128(&mut self) -> u32 {
+    pub fn read_uleb128(&mut self) -> DecodeResult<u32> {
         let mut shift = 0_u32;
         let mut val = 0_u32;
 
         loop {
-            let cur_byte = self.read_int::<u8>() as u32;
+            let cur_byte = self.read_int::<u8>()? as u32;
             val |= (cur_byte & 0b01111111) << shift;
 
             if cur_byte & 0b10000000 == 0 {
@@ -49,58 +121,58 @@ impl Reader {
             }
 
             shift += 7;
+            if shift >= 32 {
+                return Err(DecodeError::OverlongUleb128);
+            }
         }
-        val
+        Ok(val)
     }
 
     /// Reads an osu style string from the buffer.
-    pub fn read_str(&mut self) -> String {
+    pub fn read_str(&mut self) -> DecodeResult<String> {
         // Check exists byte.
-        if self.read_int::<u8>() != 0x0b {
-            return String::new();
+        match self.read_int::<u8>()? {
+            0x00 => return Ok(String::new()),
+            0x0b => (),
+            marker => return Err(DecodeError::BadStringMarker(marker)),
         }
 
         // read string len.
-        let len = self.read_uleb128() as usize;
-        let string = String::from_utf8(self.buf[self.offset..self.offset + len].into())
-            .unwrap_or(String::new());
-        self.incr_offset(len as usize);
+        let len = self.read_uleb128()? as usize;
+        let bytes = self.take(len)?;
 
-        string
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
     }
 
     /// Reads a list of i32s precremented by an u16 specifying length.
-    pub fn read_i32_list(&mut self) -> Vec<i32> {
-        let len: u16 = self.read_int();
+    pub fn read_i32_list(&mut self) -> DecodeResult<Vec<i32>> {
+        let len: u16 = self.read_int()?;
 
         if len == 0 {
-            return Vec::new();
+            return Ok(Vec::new());
         }
 
         let mut l = Vec::with_capacity(len as usize);
         for _ in 0..(len as usize) {
-            l.push(self.read_int());
+            l.push(self.read_int()?);
         }
 
-        l
+        Ok(l)
     }
 
-    pub fn read_header(&mut self) -> (i32, u32) {
-        let packet_id: u16 = self.read_int();
+    pub fn read_header(&mut self) -> DecodeResult<(i32, u32)> {
+        let packet_id: u16 = self.read_int()?;
 
-        self.incr_offset(1); // padding byte
+        self.take(1)?; // padding byte
 
-        let packet_len: u32 = self.read_int();
+        let packet_len: u32 = self.read_int()?;
 
-        return (packet_id as i32, packet_len);
+        return Ok((packet_id as i32, packet_len));
     }
 
-    pub fn read_raw(&mut self) -> Vec<u8> {
-        let len = self.buf.len() - self.offset;
-        let data = &self.buf.clone()[self.offset..self.offset + len];
-
-        self.incr_offset(len);
-        return data.to_vec();
+    pub fn read_raw(&mut self) -> DecodeResult<Vec<u8>> {
+        let len = self.remaining();
+        Ok(self.take(len)?.to_vec())
     }
 
     pub fn empty(&self) -> bool {
@@ -0,0 +1,639 @@
+use std::fmt;
+
+use crate::constants::packets::Packets;
+use crate::packets::codec::{BanchoDecode, BanchoEncode, BanchoPacket};
+use crate::packets::reader::{DecodeResult, Reader};
+
+// body types shared between several packets
+
+bancho_struct!(Message {
+    sender: String,
+    content: String,
+    recipient: String,
+    sender_id: i32,
+});
+
+bancho_struct!(ChannelInfo {
+    name: String,
+    topic: String,
+    player_count: i16,
+});
+
+const MATCH_SLOTS: usize = 16;
+const SLOT_OCCUPIED: u8 = 0b1111100; // not open, locked, or no map
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchData {
+    pub match_id: u16,
+    pub in_progress: bool,
+    pub mods: i32,
+    pub name: String,
+    pub password: String,
+    pub map_name: String,
+    pub map_id: i32,
+    pub map_md5: String,
+    pub slot_statuses: [u8; MATCH_SLOTS],
+    pub slot_teams: [u8; MATCH_SLOTS],
+    pub slot_ids: [i32; MATCH_SLOTS], // only sent for occupied slots
+    pub host_id: i32,
+    pub mode: u8,
+    pub win_condition: u8,
+    pub team_type: u8,
+    pub freemods: bool,
+    pub slot_mods: [i32; MATCH_SLOTS], // only sent with freemods on
+    pub seed: i32,
+}
+
+impl BanchoEncode for MatchData {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.match_id.encode(buf);
+        self.in_progress.encode(buf);
+        0_u8.encode(buf); // powerplay, unused
+        self.mods.encode(buf);
+        self.name.encode(buf);
+        self.password.encode(buf);
+        self.map_name.encode(buf);
+        self.map_id.encode(buf);
+        self.map_md5.encode(buf);
+
+        buf.extend_from_slice(&self.slot_statuses);
+        buf.extend_from_slice(&self.slot_teams);
+
+        for (status, id) in self.slot_statuses.iter().zip(self.slot_ids.iter()) {
+            if status & SLOT_OCCUPIED != 0 {
+                id.encode(buf);
+            }
+        }
+
+        self.host_id.encode(buf);
+        self.mode.encode(buf);
+        self.win_condition.encode(buf);
+        self.team_type.encode(buf);
+        self.freemods.encode(buf);
+
+        if self.freemods {
+            for mods in &self.slot_mods {
+                mods.encode(buf);
+            }
+        }
+
+        self.seed.encode(buf);
+    }
+}
+
+impl BanchoDecode for MatchData {
+    // read field by field in wire order, some only exist depending on earlier ones
+    #[allow(clippy::field_reassign_with_default)]
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        let mut data = Self::default();
+
+        data.match_id = reader.read()?;
+        data.in_progress = reader.read()?;
+        reader.read::<u8>()?; // powerplay, unused
+        data.mods = reader.read()?;
+        data.name = reader.read()?;
+        data.password = reader.read()?;
+        data.map_name = reader.read()?;
+        data.map_id = reader.read()?;
+        data.map_md5 = reader.read()?;
+
+        for i in 0..MATCH_SLOTS {
+            data.slot_statuses[i] = reader.read()?;
+        }
+
+        for i in 0..MATCH_SLOTS {
+            data.slot_teams[i] = reader.read()?;
+        }
+
+        for i in 0..MATCH_SLOTS {
+            if data.slot_statuses[i] & SLOT_OCCUPIED != 0 {
+                data.slot_ids[i] = reader.read()?;
+            }
+        }
+
+        data.host_id = reader.read()?;
+        data.mode = reader.read()?;
+        data.win_condition = reader.read()?;
+        data.team_type = reader.read()?;
+        data.freemods = reader.read()?;
+
+        if data.freemods {
+            for i in 0..MATCH_SLOTS {
+                data.slot_mods[i] = reader.read()?;
+            }
+        }
+
+        data.seed = reader.read()?;
+        Ok(data)
+    }
+}
+
+// the beatmap info lists use i32 length prefixes, unlike the usual u16 i32 lists
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BeatmapInfoRequestData {
+    pub filenames: Vec<String>,
+    pub ids: Vec<i32>,
+}
+
+impl BanchoEncode for BeatmapInfoRequestData {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.filenames.len() as i32).encode(buf);
+        for filename in &self.filenames {
+            filename.encode(buf);
+        }
+
+        (self.ids.len() as i32).encode(buf);
+        for id in &self.ids {
+            id.encode(buf);
+        }
+    }
+}
+
+impl BanchoDecode for BeatmapInfoRequestData {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        let mut data = Self::default();
+
+        for _ in 0..reader.read::<i32>()?.max(0) {
+            data.filenames.push(reader.read()?);
+        }
+
+        for _ in 0..reader.read::<i32>()?.max(0) {
+            data.ids.push(reader.read()?);
+        }
+
+        Ok(data)
+    }
+}
+
+bancho_struct!(BeatmapInfo {
+    index: i16,
+    beatmap_id: i32,
+    beatmapset_id: i32,
+    thread_id: i32,
+    ranked_status: i8,
+    osu_grade: u8,
+    taiko_grade: u8,
+    catch_grade: u8,
+    mania_grade: u8,
+    map_md5: String,
+});
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BeatmapInfoList(pub Vec<BeatmapInfo>);
+
+impl BanchoEncode for BeatmapInfoList {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.0.len() as i32).encode(buf);
+        for info in &self.0 {
+            info.encode(buf);
+        }
+    }
+}
+
+impl BanchoDecode for BeatmapInfoList {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        let mut infos = Vec::new();
+        for _ in 0..reader.read::<i32>()?.max(0) {
+            infos.push(reader.read()?);
+        }
+
+        Ok(Self(infos))
+    }
+}
+
+// the client's traceback, optionally followed by its config
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorReportData {
+    pub traceback: String,
+    pub config: String,
+}
+
+impl BanchoEncode for ErrorReportData {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.traceback.encode(buf);
+        self.config.encode(buf);
+    }
+}
+
+impl BanchoDecode for ErrorReportData {
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        let traceback = reader.read()?;
+
+        let config = match reader.empty() {
+            true => String::new(),
+            false => reader.read()?,
+        };
+
+        Ok(Self {
+            traceback: traceback,
+            config: config,
+        })
+    }
+}
+
+bancho_struct!(ReplayFrame {
+    button_state: u8,
+    taiko_byte: u8, // legacy, only used by very old clients
+    x: f32,
+    y: f32,
+    time: i32,
+});
+
+// the score as it stood when a frame bundle was sent
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScoreFrame {
+    pub time: i32,
+    pub id: u8,
+    pub n300: u16,
+    pub n100: u16,
+    pub n50: u16,
+    pub geki: u16,
+    pub katu: u16,
+    pub miss: u16,
+    pub total_score: i32,
+    pub max_combo: u16,
+    pub current_combo: u16,
+    pub perfect: bool,
+    pub current_hp: u8,
+    pub tag_byte: u8,
+    pub score_v2: bool,
+    pub combo_portion: f64, // only sent with score v2
+    pub bonus_portion: f64, // only sent with score v2
+}
+
+impl BanchoEncode for ScoreFrame {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.time.encode(buf);
+        self.id.encode(buf);
+        self.n300.encode(buf);
+        self.n100.encode(buf);
+        self.n50.encode(buf);
+        self.geki.encode(buf);
+        self.katu.encode(buf);
+        self.miss.encode(buf);
+        self.total_score.encode(buf);
+        self.max_combo.encode(buf);
+        self.current_combo.encode(buf);
+        self.perfect.encode(buf);
+        self.current_hp.encode(buf);
+        self.tag_byte.encode(buf);
+        self.score_v2.encode(buf);
+
+        if self.score_v2 {
+            self.combo_portion.encode(buf);
+            self.bonus_portion.encode(buf);
+        }
+    }
+}
+
+impl BanchoDecode for ScoreFrame {
+    #[allow(clippy::field_reassign_with_default)]
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        let mut frame = Self::default();
+
+        frame.time = reader.read()?;
+        frame.id = reader.read()?;
+        frame.n300 = reader.read()?;
+        frame.n100 = reader.read()?;
+        frame.n50 = reader.read()?;
+        frame.geki = reader.read()?;
+        frame.katu = reader.read()?;
+        frame.miss = reader.read()?;
+        frame.total_score = reader.read()?;
+        frame.max_combo = reader.read()?;
+        frame.current_combo = reader.read()?;
+        frame.perfect = reader.read()?;
+        frame.current_hp = reader.read()?;
+        frame.tag_byte = reader.read()?;
+        frame.score_v2 = reader.read()?;
+
+        if frame.score_v2 {
+            frame.combo_portion = reader.read()?;
+            frame.bonus_portion = reader.read()?;
+        }
+
+        Ok(frame)
+    }
+}
+
+// a spectated player's frames since their last bundle, frames have a u16 length prefix
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayFrameBundle {
+    pub extra: i32,
+    pub frames: Vec<ReplayFrame>,
+    pub action: u8, // see ReplayAction
+    pub score_frame: ScoreFrame,
+    pub sequence: u16,
+}
+
+impl BanchoEncode for ReplayFrameBundle {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.extra.encode(buf);
+
+        (self.frames.len() as u16).encode(buf);
+        for frame in &self.frames {
+            frame.encode(buf);
+        }
+
+        self.action.encode(buf);
+        self.score_frame.encode(buf);
+        self.sequence.encode(buf);
+    }
+}
+
+impl BanchoDecode for ReplayFrameBundle {
+    #[allow(clippy::field_reassign_with_default)]
+    fn decode(reader: &mut Reader) -> DecodeResult<Self> {
+        let mut bundle = Self::default();
+
+        bundle.extra = reader.read()?;
+
+        for _ in 0..reader.read::<u16>()? {
+            bundle.frames.push(reader.read()?);
+        }
+
+        bundle.action = reader.read()?;
+        bundle.score_frame = reader.read()?;
+        bundle.sequence = reader.read()?;
+
+        Ok(bundle)
+    }
+}
+
+// Packets sent by the client.
+pub mod osu {
+    use super::{BeatmapInfoRequestData, ErrorReportData, MatchData, Message, ReplayFrameBundle};
+    use crate::packets::codec::RawBytes;
+
+    bancho_struct!(OSU_CHANGE_ACTION => ChangeAction {
+        action: u8,
+        info_text: String,
+        map_md5: String,
+        mods: u32,
+        mode: u8,
+        map_id: i32,
+    });
+    bancho_struct!(OSU_SEND_PUBLIC_MESSAGE => SendPublicMessage { message: Message });
+    bancho_struct!(OSU_LOGOUT => Logout { reserved: i32 });
+    bancho_struct!(OSU_REQUEST_STATUS_UPDATE => RequestStatusUpdate {});
+    bancho_struct!(OSU_PING => Ping {});
+    bancho_struct!(OSU_START_SPECTATING => StartSpectating { target_id: i32 });
+    bancho_struct!(OSU_STOP_SPECTATING => StopSpectating {});
+    bancho_struct!(OSU_SPECTATE_FRAMES => SpectateFrames { bundle: ReplayFrameBundle });
+    bancho_struct!(OSU_ERROR_REPORT => ErrorReport { report: ErrorReportData });
+    bancho_struct!(OSU_CANT_SPECTATE => CantSpectate {});
+    bancho_struct!(OSU_SEND_PRIVATE_MESSAGE => SendPrivateMessage { message: Message });
+    bancho_struct!(OSU_PART_LOBBY => PartLobby {});
+    bancho_struct!(OSU_JOIN_LOBBY => JoinLobby {});
+    bancho_struct!(OSU_CREATE_MATCH => CreateMatch { data: MatchData });
+    bancho_struct!(OSU_JOIN_MATCH => JoinMatch { match_id: i32, password: String });
+    bancho_struct!(OSU_PART_MATCH => PartMatch {});
+    bancho_struct!(OSU_MATCH_CHANGE_SLOT => MatchChangeSlot { slot_id: i32 });
+    bancho_struct!(OSU_MATCH_READY => MatchReady {});
+    bancho_struct!(OSU_MATCH_LOCK => MatchLock { slot_id: i32 });
+    bancho_struct!(OSU_MATCH_CHANGE_SETTINGS => MatchChangeSettings { data: MatchData });
+    bancho_struct!(OSU_MATCH_START => MatchStart {});
+    bancho_struct!(OSU_MATCH_SCORE_UPDATE => MatchScoreUpdate { score_frame: RawBytes });
+    bancho_struct!(OSU_MATCH_COMPLETE => MatchComplete {});
+    bancho_struct!(OSU_MATCH_CHANGE_MODS => MatchChangeMods { mods: i32 });
+    bancho_struct!(OSU_MATCH_LOAD_COMPLETE => MatchLoadComplete {});
+    bancho_struct!(OSU_MATCH_NO_BEATMAP => MatchNoBeatmap {});
+    bancho_struct!(OSU_MATCH_NOT_READY => MatchNotReady {});
+    bancho_struct!(OSU_MATCH_FAILED => MatchFailed {});
+    bancho_struct!(OSU_MATCH_HAS_BEATMAP => MatchHasBeatmap {});
+    bancho_struct!(OSU_MATCH_SKIP_REQUEST => MatchSkipRequest {});
+    bancho_struct!(OSU_CHANNEL_JOIN => ChannelJoin { name: String });
+    bancho_struct!(OSU_BEATMAP_INFO_REQUEST => BeatmapInfoRequest { data: BeatmapInfoRequestData });
+    bancho_struct!(OSU_MATCH_TRANSFER_HOST => MatchTransferHost { slot_id: i32 });
+    bancho_struct!(OSU_FRIEND_ADD => FriendAdd { user_id: i32 });
+    bancho_struct!(OSU_FRIEND_REMOVE => FriendRemove { user_id: i32 });
+    bancho_struct!(OSU_MATCH_CHANGE_TEAM => MatchChangeTeam {});
+    bancho_struct!(OSU_CHANNEL_PART => ChannelPart { name: String });
+    bancho_struct!(OSU_RECEIVE_UPDATES => ReceiveUpdates { filter: i32 });
+    bancho_struct!(OSU_SET_AWAY_MESSAGE => SetAwayMessage { message: Message });
+    bancho_struct!(OSU_IRC_ONLY => IrcOnly {});
+    bancho_struct!(OSU_USER_STATS_REQUEST => UserStatsRequest { user_ids: Vec<i32> });
+    bancho_struct!(OSU_MATCH_INVITE => MatchInvite { user_id: i32 });
+    bancho_struct!(OSU_MATCH_CHANGE_PASSWORD => MatchChangePassword { data: MatchData });
+    bancho_struct!(OSU_TOURNAMENT_MATCH_INFO_REQUEST => TournamentMatchInfoRequest { match_id: i32 });
+    bancho_struct!(OSU_USER_PRESENCE_REQUEST => UserPresenceRequest { user_ids: Vec<i32> });
+    bancho_struct!(OSU_USER_PRESENCE_REQUEST_ALL => UserPresenceRequestAll { ingame_time: i32 });
+    bancho_struct!(OSU_TOGGLE_BLOCK_NON_FRIEND_DMS => ToggleBlockNonFriendDms { value: i32 });
+    bancho_struct!(OSU_TOURNAMENT_JOIN_MATCH_CHANNEL => TournamentJoinMatchChannel { match_id: i32 });
+    bancho_struct!(OSU_TOURNAMENT_LEAVE_MATCH_CHANNEL => TournamentLeaveMatchChannel { match_id: i32 });
+}
+
+// Packets sent by the server.
+pub mod cho {
+    use super::{BeatmapInfoList, ChannelInfo, MatchData, Message, ReplayFrameBundle};
+    use crate::packets::codec::RawBytes;
+
+    bancho_struct!(CHO_USER_ID => UserId { user_id: i32 });
+    bancho_struct!(CHO_SEND_MESSAGE => SendMessage { message: Message });
+    bancho_struct!(CHO_PONG => Pong {});
+    bancho_struct!(CHO_HANDLE_IRC_CHANGE_USERNAME => IrcChangeUsername { change: String });
+    bancho_struct!(CHO_HANDLE_IRC_QUIT => IrcQuit { username: String });
+    bancho_struct!(CHO_USER_STATS => UserStats {
+        user_id: i32,
+        action: u8,
+        info_text: String,
+        map_md5: String,
+        mods: i32,
+        mode: u8,
+        map_id: i32,
+        ranked_score: i64,
+        accuracy: f32,
+        playcount: i32,
+        total_score: i64,
+        rank: i32,
+        pp: i16,
+    });
+    bancho_struct!(CHO_USER_LOGOUT => UserLogout { user_id: i32, reserved: u8 });
+    bancho_struct!(CHO_SPECTATOR_JOINED => SpectatorJoined { user_id: i32 });
+    bancho_struct!(CHO_SPECTATOR_LEFT => SpectatorLeft { user_id: i32 });
+    bancho_struct!(CHO_SPECTATE_FRAMES => SpectateFrames { bundle: ReplayFrameBundle });
+    bancho_struct!(CHO_VERSION_UPDATE => VersionUpdate {});
+    bancho_struct!(CHO_SPECTATOR_CANT_SPECTATE => SpectatorCantSpectate { user_id: i32 });
+    bancho_struct!(CHO_GET_ATTENTION => GetAttention {});
+    bancho_struct!(CHO_NOTIFICATION => Notification { message: String });
+    bancho_struct!(CHO_UPDATE_MATCH => UpdateMatch { data: MatchData });
+    bancho_struct!(CHO_NEW_MATCH => NewMatch { data: MatchData });
+    bancho_struct!(CHO_DISPOSE_MATCH => DisposeMatch { match_id: i32 });
+    bancho_struct!(CHO_TOGGLE_BLOCK_NON_FRIEND_DMS => ToggleBlockNonFriendDms {});
+    bancho_struct!(CHO_MATCH_JOIN_SUCCESS => MatchJoinSuccess { data: MatchData });
+    bancho_struct!(CHO_MATCH_JOIN_FAIL => MatchJoinFail {});
+    bancho_struct!(CHO_FELLOW_SPECTATOR_JOINED => FellowSpectatorJoined { user_id: i32 });
+    bancho_struct!(CHO_FELLOW_SPECTATOR_LEFT => FellowSpectatorLeft { user_id: i32 });
+    bancho_struct!(CHO_ALL_PLAYERS_LOADED => AllPlayersLoaded {});
+    bancho_struct!(CHO_MATCH_START => MatchStart { data: MatchData });
+    bancho_struct!(CHO_MATCH_SCORE_UPDATE => MatchScoreUpdate { score_frame: RawBytes });
+    bancho_struct!(CHO_MATCH_TRANSFER_HOST => MatchTransferHost {});
+    bancho_struct!(CHO_MATCH_ALL_PLAYERS_LOADED => MatchAllPlayersLoaded {});
+    bancho_struct!(CHO_MATCH_PLAYER_FAILED => MatchPlayerFailed { slot_id: i32 });
+    bancho_struct!(CHO_MATCH_COMPLETE => MatchComplete {});
+    bancho_struct!(CHO_MATCH_SKIP => MatchSkip {});
+    bancho_struct!(CHO_UNAUTHORIZED => Unauthorized {});
+    bancho_struct!(CHO_CHANNEL_JOIN_SUCCESS => ChannelJoinSuccess { name: String });
+    bancho_struct!(CHO_CHANNEL_INFO => ChannelInfoPacket { info: ChannelInfo });
+    bancho_struct!(CHO_CHANNEL_KICK => ChannelKick { name: String });
+    bancho_struct!(CHO_CHANNEL_AUTO_JOIN => ChannelAutoJoin { info: ChannelInfo });
+    bancho_struct!(CHO_BEATMAP_INFO_REPLY => BeatmapInfoReply { infos: BeatmapInfoList });
+    bancho_struct!(CHO_PRIVILEGES => Privileges { privileges: i32 });
+    bancho_struct!(CHO_FRIENDS_LIST => FriendsList { user_ids: Vec<i32> });
+    bancho_struct!(CHO_PROTOCOL_VERSION => ProtocolVersion { version: i32 });
+    bancho_struct!(CHO_MAIN_MENU_ICON => MainMenuIcon { icon: String });
+    bancho_struct!(CHO_MONITOR => Monitor {});
+    bancho_struct!(CHO_MATCH_PLAYER_SKIPPED => MatchPlayerSkipped { user_id: i32 });
+    bancho_struct!(CHO_USER_PRESENCE => UserPresence {
+        user_id: i32,
+        username: String,
+        utc_offset: u8,
+        country: u8,
+        privileges: u8, // bancho privileges | mode << 5
+        longitude: f32,
+        latitude: f32,
+        rank: i32,
+    });
+    bancho_struct!(CHO_RESTART => Restart { delay_ms: i32 });
+    bancho_struct!(CHO_MATCH_INVITE => MatchInvite { message: Message });
+    bancho_struct!(CHO_CHANNEL_INFO_END => ChannelInfoEnd {});
+    bancho_struct!(CHO_MATCH_CHANGE_PASSWORD => MatchChangePassword { password: String });
+    bancho_struct!(CHO_SILENCE_END => SilenceEnd { seconds: i32 });
+    bancho_struct!(CHO_USER_SILENCED => UserSilenced { user_id: i32 });
+    bancho_struct!(CHO_USER_PRESENCE_SINGLE => UserPresenceSingle { user_id: i32 });
+    bancho_struct!(CHO_USER_PRESENCE_BUNDLE => UserPresenceBundle { user_ids: Vec<i32> });
+    bancho_struct!(CHO_USER_DM_BLOCKED => UserDmBlocked { message: Message });
+    bancho_struct!(CHO_TARGET_IS_SILENCED => TargetIsSilenced { message: Message });
+    bancho_struct!(CHO_VERSION_UPDATE_FORCED => VersionUpdateForced {});
+    bancho_struct!(CHO_SWITCH_SERVER => SwitchServer { idle_ms: i32 });
+    bancho_struct!(CHO_ACCOUNT_RESTRICTED => AccountRestricted {});
+    bancho_struct!(CHO_RTX => Rtx { message: String });
+    bancho_struct!(CHO_MATCH_ABORT => MatchAbort {});
+    bancho_struct!(CHO_SWITCH_TOURNAMENT_SERVER => SwitchTournamentServer { ip: String });
+}
+
+macro_rules! decode_table {
+    ($( $side:ident :: $name:ident ),* $(,)?) => {
+        // Decodes the body of any packet by id, for tooling that doesn't care about the concrete type.
+        pub fn decode_packet(packet: Packets, reader: &mut Reader) -> DecodeResult<Box<dyn fmt::Debug>> {
+            $(
+                if packet == <$side::$name as BanchoPacket>::ID {
+                    return Ok(Box::new(reader.read::<$side::$name>()?));
+                }
+            )*
+
+            unreachable!("every packet has a struct");
+        }
+    };
+}
+
+decode_table! {
+    osu::ChangeAction,
+    osu::SendPublicMessage,
+    osu::Logout,
+    osu::RequestStatusUpdate,
+    osu::Ping,
+    osu::StartSpectating,
+    osu::StopSpectating,
+    osu::SpectateFrames,
+    osu::ErrorReport,
+    osu::CantSpectate,
+    osu::SendPrivateMessage,
+    osu::PartLobby,
+    osu::JoinLobby,
+    osu::CreateMatch,
+    osu::JoinMatch,
+    osu::PartMatch,
+    osu::MatchChangeSlot,
+    osu::MatchReady,
+    osu::MatchLock,
+    osu::MatchChangeSettings,
+    osu::MatchStart,
+    osu::MatchScoreUpdate,
+    osu::MatchComplete,
+    osu::MatchChangeMods,
+    osu::MatchLoadComplete,
+    osu::MatchNoBeatmap,
+    osu::MatchNotReady,
+    osu::MatchFailed,
+    osu::MatchHasBeatmap,
+    osu::MatchSkipRequest,
+    osu::ChannelJoin,
+    osu::BeatmapInfoRequest,
+    osu::MatchTransferHost,
+    osu::FriendAdd,
+    osu::FriendRemove,
+    osu::MatchChangeTeam,
+    osu::ChannelPart,
+    osu::ReceiveUpdates,
+    osu::SetAwayMessage,
+    osu::IrcOnly,
+    osu::UserStatsRequest,
+    osu::MatchInvite,
+    osu::MatchChangePassword,
+    osu::TournamentMatchInfoRequest,
+    osu::UserPresenceRequest,
+    osu::UserPresenceRequestAll,
+    osu::ToggleBlockNonFriendDms,
+    osu::TournamentJoinMatchChannel,
+    osu::TournamentLeaveMatchChannel,
+    cho::UserId,
+    cho::SendMessage,
+    cho::Pong,
+    cho::IrcChangeUsername,
+    cho::IrcQuit,
+    cho::UserStats,
+    cho::UserLogout,
+    cho::SpectatorJoined,
+    cho::SpectatorLeft,
+    cho::SpectateFrames,
+    cho::VersionUpdate,
+    cho::SpectatorCantSpectate,
+    cho::GetAttention,
+    cho::Notification,
+    cho::UpdateMatch,
+    cho::NewMatch,
+    cho::DisposeMatch,
+    cho::ToggleBlockNonFriendDms,
+    cho::MatchJoinSuccess,
+    cho::MatchJoinFail,
+    cho::FellowSpectatorJoined,
+    cho::FellowSpectatorLeft,
+    cho::AllPlayersLoaded,
+    cho::MatchStart,
+    cho::MatchScoreUpdate,
+    cho::MatchTransferHost,
+    cho::MatchAllPlayersLoaded,
+    cho::MatchPlayerFailed,
+    cho::MatchComplete,
+    cho::MatchSkip,
+    cho::Unauthorized,
+    cho::ChannelJoinSuccess,
+    cho::ChannelInfoPacket,
+    cho::ChannelKick,
+    cho::ChannelAutoJoin,
+    cho::BeatmapInfoReply,
+    cho::Privileges,
+    cho::FriendsList,
+    cho::ProtocolVersion,
+    cho::MainMenuIcon,
+    cho::Monitor,
+    cho::MatchPlayerSkipped,
+    cho::UserPresence,
+    cho::Restart,
+    cho::MatchInvite,
+    cho::ChannelInfoEnd,
+    cho::MatchChangePassword,
+    cho::SilenceEnd,
+    cho::UserSilenced,
+    cho::UserPresenceSingle,
+    cho::UserPresenceBundle,
+    cho::UserDmBlocked,
+    cho::TargetIsSilenced,
+    cho::VersionUpdateForced,
+    cho::SwitchServer,
+    cho::AccountRestricted,
+    cho::Rtx,
+    cho::MatchAbort,
+    cho::SwitchTournamentServer,
+}
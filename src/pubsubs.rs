@@ -1,12 +1,20 @@
 use futures::StreamExt;
 use serde_json::Value;
 use std::str::FromStr;
+use tracing::{info, warn};
 
+use crate::metrics;
+use crate::objects::settings;
 use crate::packets::handlers;
-use crate::{bcrypt_cache, players, redis};
+use crate::{captures, players, recordings, redis};
+
+// every instance in a cluster gets these, only the one serving the user acts on them
 
 async fn ban_handler(user_id: i32) {
-    let _user = players.get_id(user_id).await.unwrap();
+    let _user = match players.get_id(user_id) {
+        Some(user) => user,
+        _ => return,
+    };
     let mut user = _user.write().await;
 
     user.handle_restriction().await; // generic function moment
@@ -18,13 +26,57 @@ async fn bot_msg_handler(raw: &str) {
     unimplemented!(); // TODO: actually send msg
 }
 
+async fn capture_handler(raw: &str) {
+    // userID (optional, global if missing), enabled
+    let data: Value = match serde_json::from_str(raw) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!(error = %e, raw, "bad capture message");
+            return;
+        }
+    };
+
+    let enabled = match data["enabled"].as_bool() {
+        Some(enabled) => enabled,
+        _ => {
+            warn!(raw, "capture message without enabled");
+            return;
+        }
+    };
+
+    // a userID that isn't a number mustn't fall through to capturing everyone
+    match (&data["userID"], data["userID"].as_i64()) {
+        (_, Some(user_id)) if enabled => captures.enable(user_id as i32),
+        (_, Some(user_id)) => captures.disable(user_id as i32),
+        (Value::Null, _) => captures.set_global(enabled),
+        _ => warn!(raw, "capture message with a bad userID"),
+    }
+}
+
+async fn record_handler(raw: &str) {
+    // userID, enabled
+    let data: Value = match serde_json::from_str(raw) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!(error = %e, raw, "bad record message");
+            return;
+        }
+    };
+
+    match (data["userID"].as_i64(), data["enabled"].as_bool()) {
+        (Some(user_id), Some(true)) => recordings.enable(user_id as i32),
+        (Some(user_id), Some(false)) => recordings.disable(user_id as i32),
+        _ => warn!(raw, "record message needs a userID and enabled"),
+    }
+}
+
 async fn change_username_handler(raw: &str) {
     let data: Value = serde_json::from_str(raw).unwrap(); // userID, newUsername
 
-    let _user = players
-        .get_id(data["userID"].as_i64().unwrap() as i32)
-        .await
-        .unwrap();
+    let _user = match players.get_id(data["userID"].as_i64().unwrap() as i32) {
+        Some(user) => user,
+        _ => return,
+    };
     let user = _user.read().await;
 
     let mut packet_bytes = handlers::notification(&format!(
@@ -40,31 +92,54 @@ async fn change_username_handler(raw: &str) {
 async fn disconnect_handler(raw: &str) {
     let data: Value = serde_json::from_str(raw).unwrap(); // userID, reason
 
-    unimplemented!(); // TODO: handle kicks
+    let _user = match players.get_id(data["userID"].as_i64().unwrap() as i32) {
+        Some(user) => user,
+        _ => return,
+    };
+    let mut user = _user.write().await;
+
+    user.kick(
+        data["reason"]
+            .as_str()
+            .unwrap_or("You have been kicked from the server."),
+    )
+    .await;
 }
 
 async fn notification_handler(raw: &str) {
     let data: Value = serde_json::from_str(raw).unwrap(); // userID, message
 
-    let _user = players
-        .get_id(data["userID"].as_i64().unwrap() as i32)
-        .await
-        .unwrap();
+    let _user = match players.get_id(data["userID"].as_i64().unwrap() as i32) {
+        Some(user) => user,
+        _ => return,
+    };
     let user = _user.read().await;
 
     user.enqueue(handlers::notification(data["message"].as_str().unwrap()))
         .await;
 }
 
+async fn maintenance_handler(raw: &str) {
+    settings::set_maintenance(raw == "1").await;
+}
+
+async fn reload_settings_handler() {
+    settings::reload_settings().await;
+}
+
 pub async fn initialise_pubsubs() {
     let conn = redis.get().unwrap().get_async_connection().await.unwrap();
     let mut pubsub_conn = conn.into_pubsub();
 
-    for pubsub in vec![
+    for pubsub in [
         "peppy:ban",
         "peppy:bot_msg",
+        "peppy:capture",
         "peppy:disconnect",
+        "peppy:maintenance",
         "peppy:notification",
+        "peppy:record",
+        "peppy:reload_settings",
     ] {
         pubsub_conn.subscribe(pubsub).await.unwrap();
     }
@@ -74,15 +149,20 @@ pub async fn initialise_pubsubs() {
         let channel = msg.get_channel_name();
 
         let content: String = msg.get_payload().unwrap();
+        metrics::PUBSUB_MESSAGES.with_label_values(&[channel]).inc();
 
         match channel {
             "peppy:ban" => ban_handler(i32::from_str(&content).unwrap()).await,
             "peppy:bot_msg" => bot_msg_handler(&content).await,
+            "peppy:capture" => capture_handler(&content).await,
             "peppy:disconnect" => disconnect_handler(&content).await,
+            "peppy:maintenance" => maintenance_handler(&content).await,
             "peppy:notification" => notification_handler(&content).await,
+            "peppy:record" => record_handler(&content).await,
+            "peppy:reload_settings" => reload_settings_handler().await,
             _ => continue,
         };
 
-        println!("Handled {} with content {}", channel, content);
+        info!(channel, %content, "handled pubsub message");
     }
 }
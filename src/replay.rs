@@ -0,0 +1,159 @@
+use std::io;
+
+use crate::packets::codec::BanchoEncode;
+use crate::packets::structs::{ReplayFrame, ScoreFrame};
+
+// .osr replay files, laid out like the client writes them:
+// [mode u8][version i32][map md5][username][replay md5][300s, 100s, 50s, gekis, katus, misses u16]
+// [score i32][max combo u16][perfect u8][mods i32][life bar][timestamp i64][data length i32][lzma data][score id i64]
+// strings are osu strings, the timestamp is in windows ticks and the data is the lzma'd frames.
+
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+pub struct Replay {
+    pub mode: u8, // vanilla mode
+    pub version: i32,
+    pub map_md5: String,
+    pub username: String,
+    pub score: ScoreFrame,
+    pub mods: i32,
+    pub timestamp: i64, // unix time
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    // The frames the way the client stores them, `time since last frame|x|y|keys` joined with commas.
+    pub fn frame_data(&self) -> String {
+        let mut last_time = 0;
+        let mut frames = Vec::with_capacity(self.frames.len());
+
+        for frame in &self.frames {
+            frames.push(format!(
+                "{}|{}|{}|{}",
+                frame.time - last_time,
+                frame.x,
+                frame.y,
+                frame.button_state
+            ));
+            last_time = frame.time;
+        }
+
+        return frames.join(",");
+    }
+
+    pub fn serialise(&self) -> io::Result<Vec<u8>> {
+        let frame_data = self.frame_data();
+
+        let mut compressed = Vec::new();
+        lzma_rs::lzma_compress(&mut frame_data.as_bytes(), &mut compressed)?;
+
+        // nothing checks this, it just has to tell replays apart
+        let replay_md5 = format!("{:x}", md5::compute(&frame_data));
+
+        let mut buf = Vec::with_capacity(compressed.len() + 128);
+        self.mode.encode(&mut buf);
+        self.version.encode(&mut buf);
+        self.map_md5.encode(&mut buf);
+        self.username.encode(&mut buf);
+        replay_md5.encode(&mut buf);
+        self.score.n300.encode(&mut buf);
+        self.score.n100.encode(&mut buf);
+        self.score.n50.encode(&mut buf);
+        self.score.geki.encode(&mut buf);
+        self.score.katu.encode(&mut buf);
+        self.score.miss.encode(&mut buf);
+        self.score.total_score.encode(&mut buf);
+        self.score.max_combo.encode(&mut buf);
+        self.score.perfect.encode(&mut buf);
+        self.mods.encode(&mut buf);
+        "".encode(&mut buf); // life bar graph
+        (UNIX_EPOCH_TICKS + self.timestamp * TICKS_PER_SECOND).encode(&mut buf);
+        (compressed.len() as i32).encode(&mut buf);
+        buf.extend_from_slice(&compressed);
+        0_i64.encode(&mut buf); // online score id, it was never submitted
+
+        return Ok(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::reader::Reader;
+
+    fn frame(time: i32, x: f32, y: f32, button_state: u8) -> ReplayFrame {
+        return ReplayFrame {
+            button_state: button_state,
+            taiko_byte: 0,
+            x: x,
+            y: y,
+            time: time,
+        };
+    }
+
+    fn sample_replay() -> Replay {
+        return Replay {
+            mode: 0,
+            version: 20220101,
+            map_md5: "da8aae79c8f3306b5d65ec951874a7fb".to_string(),
+            username: "tsunyoku".to_string(),
+            score: ScoreFrame {
+                n300: 12,
+                n100: 1,
+                miss: 1,
+                total_score: 31337,
+                max_combo: 13,
+                ..Default::default()
+            },
+            mods: 72,
+            timestamp: 1_640_995_200, // 2022-01-01
+            frames: vec![
+                frame(1000, 256.0, 192.0, 1),
+                frame(1016, 300.5, 100.25, 0),
+                frame(1033, 301.0, 101.0, 5),
+            ],
+        };
+    }
+
+    #[test]
+    fn frame_data_uses_time_deltas() {
+        assert_eq!(
+            sample_replay().frame_data(),
+            "1000|256|192|1,16|300.5|100.25|0,17|301|101|5"
+        );
+    }
+
+    #[test]
+    fn osr_layout() {
+        let replay = sample_replay();
+        let mut reader = Reader::new(replay.serialise().unwrap());
+
+        assert_eq!(reader.read::<u8>().unwrap(), 0);
+        assert_eq!(reader.read::<i32>().unwrap(), 20220101);
+        assert_eq!(reader.read::<String>().unwrap(), replay.map_md5);
+        assert_eq!(reader.read::<String>().unwrap(), "tsunyoku");
+        assert_eq!(reader.read::<String>().unwrap().len(), 32);
+
+        for expected in &[12, 1, 0, 0, 0, 1] {
+            assert_eq!(reader.read::<u16>().unwrap(), *expected);
+        }
+
+        assert_eq!(reader.read::<i32>().unwrap(), 31337);
+        assert_eq!(reader.read::<u16>().unwrap(), 13);
+        assert!(!reader.read::<bool>().unwrap());
+        assert_eq!(reader.read::<i32>().unwrap(), 72);
+        assert_eq!(reader.read::<String>().unwrap(), "");
+        assert_eq!(reader.read::<i64>().unwrap(), 637_765_920_000_000_000);
+
+        let len = reader.read::<i32>().unwrap() as usize;
+        let compressed = reader.read_packet(len).unwrap().read_raw().unwrap();
+
+        let mut frame_data = Vec::new();
+        lzma_rs::lzma_decompress(&mut compressed.as_slice(), &mut frame_data).unwrap();
+        assert_eq!(String::from_utf8(frame_data).unwrap(), replay.frame_data());
+
+        assert_eq!(reader.read::<i64>().unwrap(), 0);
+        assert!(reader.empty());
+    }
+}
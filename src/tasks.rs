@@ -0,0 +1,97 @@
+use ntex::server::Server;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::info;
+
+use crate::cluster;
+use crate::packets::handlers;
+use crate::{pending_writes, players, shutting_down};
+
+const SESSION_TIMEOUT: Duration = Duration::from_secs(100);
+const REAPER_INTERVAL: Duration = Duration::from_secs(10);
+
+const WRITE_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+// how long clients wait before reconnecting after a shutdown
+pub const RECONNECT_DELAY_MS: i32 = 5000;
+// roughly the longest an idle client goes between polls
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Logs out any session that hasn't made a request within the timeout,
+// e.g. a client that crashed without sending OSU_LOGOUT. the rest are kept alive in the cluster.
+// users are only read locked to check, so this doesn't hold up their requests.
+pub async fn reap_idle_sessions() {
+    let mut interval = tokio::time::interval(REAPER_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let mut alive = Vec::new();
+        for u in players.all() {
+            let expired = {
+                let user = u.read().await;
+                let expired = user.last_request.elapsed() >= SESSION_TIMEOUT;
+
+                if !expired {
+                    alive.push((user.id, user.token.clone(), !user.restricted()));
+                }
+
+                expired
+            };
+
+            if !expired {
+                continue;
+            }
+
+            let mut user = u.write().await;
+
+            // they might have made a request while we were waiting for the lock
+            if user.last_request.elapsed() < SESSION_TIMEOUT {
+                continue;
+            }
+
+            user.logout().await;
+            info!(user_id = user.id, username = %user.username, "session timed out");
+        }
+
+        cluster::refresh_sessions(&alive).await;
+    }
+}
+
+pub async fn flush_pending_writes() {
+    let mut interval = tokio::time::interval(WRITE_FLUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        pending_writes.flush().await;
+    }
+}
+
+// Waits for SIGINT/SIGTERM, then tells every client to reconnect shortly
+// and stops the server once they've had the chance to receive that.
+pub async fn shutdown_on_signal(server: Server) {
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = sigterm.recv() => {},
+    }
+
+    info!(
+        players = players.player_count(),
+        "shutting down, telling players to reconnect"
+    );
+
+    // stop accepting logins, then restart everyone already online
+    shutting_down.store(true, Ordering::SeqCst);
+    players
+        .enqueue(handlers::server_restart(RECONNECT_DELAY_MS))
+        .await;
+
+    // give every client a poll to drain their queue
+    tokio::time::sleep(CLIENT_POLL_INTERVAL).await;
+
+    pending_writes.flush().await;
+    server.stop(true).await;
+}